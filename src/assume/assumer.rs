@@ -1,80 +1,139 @@
-use chrono::{Duration, Utc};
+use chrono::Utc;
 use rusoto_core::{HttpClient, Region};
 use rusoto_credential::{AwsCredentials, StaticProvider};
 use rusoto_sts::{
-    AssumeRoleRequest, GetSessionTokenRequest, NewAwsCredsForStsCreds, Sts, StsClient,
+    AssumeRoleRequest, AssumeRoleWithSAMLRequest, AssumeRoleWithWebIdentityRequest,
+    GetSessionTokenRequest, NewAwsCredsForStsCreds, PolicyDescriptorType, Sts, StsClient, Tag,
 };
 
+use super::sso::SsoClient;
 use crate::config::{AssumeSubject, Config};
 use crate::credentials::{CredentialsFile, ProfileName};
 
 pub struct RoleAssumer<'a> {
-    region: Region,
     store: &'a mut CredentialsFile,
+    cache: Option<&'a mut CredentialsFile>,
     config: &'a Config,
-}
-
-struct Cred {
-    key: String,
-    secret: String,
-    token: Option<String>,
-}
-
-impl From<&AwsCredentials> for Cred {
-    fn from(c: &AwsCredentials) -> Self {
-        Cred {
-            key: c.aws_access_key_id().to_owned(),
-            secret: c.aws_secret_access_key().to_owned(),
-            token: c.token().clone(),
-        }
-    }
+    force: bool,
+    write: bool,
 }
 
 impl<'a> RoleAssumer<'a> {
-    pub fn new(region: Region, store: &'a mut CredentialsFile, config: &'a Config) -> Self {
+    /// With `force` all cached temporary credentials are ignored and requested anew.
+    pub fn new(store: &'a mut CredentialsFile, config: &'a Config, force: bool) -> Self {
         Self {
-            region,
             store,
+            cache: None,
             config,
+            force,
+            write: true,
         }
     }
 
-    pub fn assume(&mut self, profile: &str) -> Result<(), String> {
+    /// Keeps new credentials in memory only, the credentials file is not written.
+    pub fn without_writing(mut self) -> Self {
+        self.write = false;
+        self
+    }
+
+    /// Also uses and keeps the temporary credentials in `cache`,
+    /// e.g. if the credentials file is not written.
+    pub fn with_cache(mut self, cache: &'a mut CredentialsFile) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Returns the credentials of the profile, requesting new ones if needed.
+    pub fn assume(&mut self, profile: &str) -> Result<AwsCredentials, String> {
+        let _lock = self.store.lock()?;
+        self.store.reload()?;
+        let store = &mut *self.store;
+        let _cache_lock = match self.cache.as_deref_mut() {
+            Some(cache) => {
+                let lock = cache.lock()?;
+                cache.reload()?;
+                for name in cache.temporary_profiles() {
+                    let cached = cache.get_credentials(&name).cloned();
+                    if let Some(cred) = cached.filter(|c| outlives(c, store.get_credentials(&name)))
+                    {
+                        store.put_credentials(name, cred);
+                    }
+                }
+                Some(lock)
+            }
+            None => None,
+        };
         let pn = ProfileName::new(profile.to_owned());
-        self.profile_credentials(&pn).map(|_| ())?;
-        self.store.write()
+        let cred = self.profile_credentials(&pn)?;
+        if self.write {
+            self.store.write()?;
+        }
+        if let Some(cache) = self.cache.as_deref() {
+            cache.write()?;
+        }
+        Ok(cred)
     }
 
-    fn profile_credentials(&mut self, profile: &ProfileName) -> Result<Cred, String> {
+    fn profile_credentials(&mut self, profile: &ProfileName) -> Result<AwsCredentials, String> {
+        let refresh_before = self.config.refresh_before_expiry_of(profile);
+        let force = self.force && profile != &self.config.main_profile;
         match self.store.get_credentials(profile) {
+            Some(_) if force => self.get_new_credentials(profile),
             Some(cred) => match cred.expires_at() {
-                Some(exp) if *exp - Utc::now() < Duration::minutes(10) =>
-                // TODO: make the time configurable
-                {
+                Some(exp) if *exp - Utc::now() < refresh_before => {
                     self.get_new_credentials(profile)
                 }
-                _ => Ok(cred.into()),
+                _ => Ok(cred.clone()),
             },
             None => self.get_new_credentials(profile),
         }
     }
 
-    fn get_new_credentials(&mut self, profile: &ProfileName) -> Result<Cred, String> {
-        let parent = self
-            .config
-            .parent_profile(profile)
-            .ok_or(format!("profile '{}' does not exist", &profile))?
-            .clone();
-        let parent_cred = self.profile_credentials(&parent)?;
+    fn get_new_credentials(&mut self, profile: &ProfileName) -> Result<AwsCredentials, String> {
+        if profile == &self.config.main_profile {
+            return Err(format!("You specified main_profile=\"{prof}\" but there is no profile with this name in your credentials file ", prof = profile));
+        }
+        let parent_cred = match self.config.parent_profile(profile) {
+            Some(parent) => Some(self.profile_credentials(&parent.clone())?),
+            None => None,
+        };
         let sub = self
             .config
             .assume_subject(profile)?
-            .ok_or(format!("cannot get assume subject for profile {}", profile))?;
-        let parent_client = create_sts_client(parent_cred, self.region.clone())?;
-        let new_cred = assume_subject(&parent_client, sub)?;
-        let out_cred = (&new_cred).into();
-        self.store.put_credentials(profile.clone(), new_cred);
-        Ok(out_cred)
+            .ok_or(format!("profile '{}' does not exist", profile))?;
+        let new_cred = match sub {
+            AssumeSubject::Sso {
+                account_id,
+                role_name,
+            } => {
+                let sso_config = self
+                    .config
+                    .sso
+                    .as_ref()
+                    .ok_or("SSO profiles require the [sso] section")?;
+                SsoClient::new(sso_config)?.role_credentials(&account_id, &role_name)?
+            }
+            sub => {
+                let client =
+                    create_sts_client(parent_cred, self.config.region_of(profile).clone())?;
+                assume_subject(&client, sub)?
+            }
+        };
+        self.store
+            .put_credentials(profile.clone(), new_cred.clone());
+        if let Some(cache) = self.cache.as_deref_mut() {
+            cache.put_credentials(profile.clone(), new_cred.clone());
+        }
+        Ok(new_cred)
+    }
+}
+
+/// Whether the credentials expire later than the other ones, if there are any.
+fn outlives(cred: &AwsCredentials, other: Option<&AwsCredentials>) -> bool {
+    match other.map(|o| o.expires_at()) {
+        None => true,
+        Some(None) => false,
+        Some(Some(other)) => cred.expires_at().is_some_and(|exp| exp > *other),
     }
 }
 
@@ -84,10 +143,36 @@ fn assume_subject(client: &StsClient, subject: AssumeSubject) -> Result<AwsCrede
         AssumeSubject::Role {
             role_arn,
             session_name,
+            duration,
+            external_id,
+            source_identity,
+            tags,
+            transitive_tag_keys,
+            policy_arns,
+            policy,
         } => {
             let req = AssumeRoleRequest {
                 role_arn,
                 role_session_name: session_name,
+                duration_seconds: duration.map(|d| d.num_seconds()),
+                external_id,
+                source_identity,
+                tags: if tags.is_empty() {
+                    None
+                } else {
+                    Some(
+                        tags.into_iter()
+                            .map(|(key, value)| Tag { key, value })
+                            .collect(),
+                    )
+                },
+                transitive_tag_keys: if transitive_tag_keys.is_empty() {
+                    None
+                } else {
+                    Some(transitive_tag_keys)
+                },
+                policy_arns: policy_descriptors(policy_arns),
+                policy,
                 ..Default::default()
             };
             let result = runtime.block_on(async move {
@@ -103,11 +188,12 @@ fn assume_subject(client: &StsClient, subject: AssumeSubject) -> Result<AwsCrede
         AssumeSubject::MfaSession {
             serial_number,
             token_code,
+            duration,
         } => {
             let req = GetSessionTokenRequest {
                 serial_number: Some(serial_number),
                 token_code: Some(token_code),
-                duration_seconds: None,
+                duration_seconds: duration.map(|d| d.num_seconds()),
             };
             let result = runtime.block_on(async move {
                 client
@@ -120,16 +206,146 @@ fn assume_subject(client: &StsClient, subject: AssumeSubject) -> Result<AwsCrede
                 .credentials
                 .expect("STS successful response contains None credentials")
         }
+        AssumeSubject::WebIdentity {
+            role_arn,
+            session_name,
+            token,
+            duration,
+            policy_arns,
+            policy,
+        } => {
+            let req = AssumeRoleWithWebIdentityRequest {
+                role_arn,
+                role_session_name: session_name,
+                web_identity_token: token,
+                duration_seconds: duration.map(|d| d.num_seconds()),
+                policy_arns: policy_descriptors(policy_arns),
+                policy,
+                ..Default::default()
+            };
+            let result = runtime.block_on(async move {
+                client
+                    .assume_role_with_web_identity(req)
+                    .await
+                    .map_err(|e| format!("unable to assume role with web identity: {}", e))
+            })?;
+            result
+                .credentials
+                .expect("STS successful response contains None credentials")
+        }
+        AssumeSubject::Saml {
+            role_arn,
+            principal_arn,
+            assertion,
+            duration,
+            policy_arns,
+            policy,
+        } => {
+            let req = AssumeRoleWithSAMLRequest {
+                role_arn,
+                principal_arn,
+                saml_assertion: assertion,
+                duration_seconds: duration.map(|d| d.num_seconds()),
+                policy_arns: policy_descriptors(policy_arns),
+                policy,
+            };
+            let result = runtime.block_on(async move {
+                client
+                    .assume_role_with_saml(req)
+                    .await
+                    .map_err(|e| format!("unable to assume role with SAML: {}", e))
+            })?;
+            result
+                .credentials
+                .expect("STS successful response contains None credentials")
+        }
+        AssumeSubject::Sso { .. } => unreachable!("SSO credentials do not come from STS"),
     };
 
     AwsCredentials::new_for_credentials(cred)
         .map_err(|e| format!("Cannot create AwsCredentials from STS credentials: {}", e))
 }
 
-fn create_sts_client(credentials: Cred, region: Region) -> Result<StsClient, String> {
+fn policy_descriptors(policy_arns: Vec<String>) -> Option<Vec<PolicyDescriptorType>> {
+    if policy_arns.is_empty() {
+        None
+    } else {
+        Some(
+            policy_arns
+                .into_iter()
+                .map(|arn| PolicyDescriptorType { arn: Some(arn) })
+                .collect(),
+        )
+    }
+}
+
+/// Without credentials the client sends unsigned requests.
+pub(super) fn create_sts_client(
+    credentials: Option<AwsCredentials>,
+    region: Region,
+) -> Result<StsClient, String> {
     Ok(StsClient::new_with(
         HttpClient::from_connector(super::get_https_connector()?),
-        StaticProvider::new(credentials.key, credentials.secret, credentials.token, None),
+        StaticProvider::from(credentials.unwrap_or_default()),
         region,
     ))
 }
+
+#[test]
+fn cache_keeps_credentials_file_untouched() {
+    use chrono::Duration;
+    use std::fs;
+    use std::path::Path;
+
+    const CONFIG_PATH: &str = "./test.cache.config";
+    const PATH: &str = "./test.cache.credentials";
+    const EXPIRATIONS_PATH: &str = "./test.cache.expirations.toml";
+    const CACHE_PATH: &str = "./test.cache.cache";
+    const CACHE_EXPIRATIONS_PATH: &str = "./test.cache.cache-expirations.toml";
+
+    fs::write(
+        CONFIG_PATH,
+        "mfa_serial_number = 'mfa'\n[profiles]\ndev = 'arn:aws:iam::1:role/Dev'\n",
+    )
+    .unwrap();
+    let config = Config::read_raw(Path::new(CONFIG_PATH));
+    fs::remove_file(CONFIG_PATH).unwrap();
+    let config = config.unwrap().unwrap();
+    let original =
+        "# hand-written\n[main]\naws_access_key_id = AKIAMAIN\naws_secret_access_key = secret\n";
+    fs::write(PATH, original).unwrap();
+    let mut cache = CredentialsFile::read(CACHE_PATH, CACHE_EXPIRATIONS_PATH).unwrap();
+    cache.put_credentials(
+        ProfileName::new("dev"),
+        AwsCredentials::new(
+            "ASIADEV",
+            "dev-secret",
+            Some("dev-token".to_owned()),
+            Some(Utc::now() + Duration::hours(1)),
+        ),
+    );
+    cache.write().unwrap();
+
+    let mut store = CredentialsFile::read(PATH, EXPIRATIONS_PATH).unwrap();
+    let mut cache = CredentialsFile::read(CACHE_PATH, CACHE_EXPIRATIONS_PATH).unwrap();
+    let cred = RoleAssumer::new(&mut store, &config, false)
+        .without_writing()
+        .with_cache(&mut cache)
+        .assume("dev")
+        .unwrap();
+    let content = fs::read_to_string(PATH).unwrap();
+    let expirations_written = Path::new(EXPIRATIONS_PATH).exists();
+    for path in [
+        PATH,
+        CACHE_PATH,
+        CACHE_EXPIRATIONS_PATH,
+        "./test.cache.expirations.lock",
+        "./test.cache.cache-expirations.lock",
+    ] {
+        let _ = fs::remove_file(path);
+    }
+
+    assert_eq!(cred.aws_access_key_id(), "ASIADEV");
+    assert_eq!(content, original);
+    assert!(!expirations_written);
+}
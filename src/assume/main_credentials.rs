@@ -14,19 +14,30 @@ pub fn rotate_if_needed(
     state: &mut State,
 ) -> Result<(), String> {
     if let Some(days) = config.rotate_credentials_days {
-        let now = Utc::now();
-        let last_rotation = state
-            .last_credentials_rotation_time
-            .unwrap_or_else(|| Utc.timestamp(0, 0));
-        if now - last_rotation >= Duration::days(days) {
-            rotate_credentials(cred_file, config)?;
-            state.last_credentials_rotation_time = Some(now);
-            state.save()?;
+        if !rotation_due(state, days) {
+            return Ok(());
         }
+        let _lock = cred_file.lock()?;
+        // A concurrent run may have rotated the key while we were waiting for the lock.
+        *state = State::read();
+        if !rotation_due(state, days) {
+            return Ok(());
+        }
+        cred_file.reload()?;
+        rotate_credentials(cred_file, config)?;
+        state.last_credentials_rotation_time = Some(Utc::now());
+        state.save()?;
     }
     Ok(())
 }
 
+fn rotation_due(state: &State, days: i64) -> bool {
+    let last_rotation = state
+        .last_credentials_rotation_time
+        .unwrap_or_else(|| Utc.timestamp(0, 0));
+    Utc::now() - last_rotation >= Duration::days(days)
+}
+
 fn rotate_credentials(cred_file: &mut CredentialsFile, config: &Config) -> Result<(), String> {
     let (client, access_key) = {
         let cred = cred_file
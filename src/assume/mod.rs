@@ -2,30 +2,37 @@ use std::path::Path;
 use std::{env, process};
 
 use ansi_term::{Color, Style};
-use chrono::{Duration, Utc};
+use chrono::{Duration, SecondsFormat, Utc};
 use hyper::client::HttpConnector;
 use hyper::Uri;
 use hyper_proxy::{Intercept, Proxy, ProxyConnector};
 use hyper_tls::HttpsConnector;
+use rusoto_credential::AwsCredentials;
+use serde_json::json;
 
 use crate::assume::assumer::RoleAssumer;
-use crate::config::Config;
-use crate::credentials::CredentialsFile;
-use crate::init::SHELL_VAR;
+use crate::config::{Config, ExportMode};
+use crate::credentials::{CredentialsFile, ProfileName};
+use crate::init::{self, PROFILE_VAR, SHELL_VAR};
 use crate::util;
-use crate::{state, styles};
+use crate::{aws_config, state, styles};
 use tokio::runtime::{Builder, Runtime};
 
 mod assumer;
 mod main_credentials;
+mod sso;
+mod whoami;
 
-pub fn run(profile: &str, config: &Config) {
+pub use sso::token_cache_path as sso_token_cache_path;
+pub use whoami::run as whoami;
+
+pub fn run(profile: &str, force: bool, export: ExportMode, config: &Config) {
     let error = util::styled_error_word();
     if outdated_script() {
         print_update_instructions();
         process::exit(5);
     }
-    match run_raw(profile, config) {
+    match run_raw(profile, force, export, config) {
         Ok(_) => {}
         Err(e) => {
             eprintln!("{}: {}", &error, e);
@@ -34,16 +41,16 @@ pub fn run(profile: &str, config: &Config) {
     }
 }
 
-fn run_raw(profile: &str, config: &Config) -> Result<(), String> {
+fn run_raw(profile: &str, force: bool, export: ExportMode, config: &Config) -> Result<(), String> {
     let mut cred_file = CredentialsFile::read_default()?;
-    if cred_file.get_credentials(&config.main_profile).is_none() {
-        return Err(format!("You specified main_profile=\"{prof}\" but there is no profile with this name in your credentials file ", prof = &config.main_profile));
-    }
     let mut state = state::State::read();
 
-    let mut assumer = RoleAssumer::new(config.region.clone(), &mut cred_file, config);
-    assumer.assume(profile)?;
-    print_profile(profile, config);
+    let mut assumer = RoleAssumer::new(&mut cred_file, config, force);
+    let cred = assumer.assume(profile)?;
+    if config.update_aws_config {
+        aws_config::update(config)?;
+    }
+    print_profile(&profile_env(profile, &cred, export, config), config);
 
     if let Some(check_every_days) = config.check_new_version_interval_days {
         if Utc::now() - state.last_version_check_time > Duration::days(check_every_days as i64) {
@@ -53,11 +60,128 @@ fn run_raw(profile: &str, config: &Config) -> Result<(), String> {
         }
     }
 
-    main_credentials::rotate_if_needed(config, &mut cred_file, &mut state)?;
+    // SSO, web identity and SAML users may have no main credentials at all
+    if cred_file.get_credentials(&config.main_profile).is_some() {
+        main_credentials::rotate_if_needed(config, &mut cred_file, &mut state)?;
+    }
 
     Ok(())
 }
 
+/// Prints the credentials as JSON document for the `credential_process` setting of the AWS tools.
+/// Nothing else is printed on stdout.
+pub fn credential_process(profile: &str, config: &Config) {
+    let result = CredentialsFile::read_default().and_then(|mut cred_file| {
+        let mut cache = credential_process_cache()?;
+        RoleAssumer::new(&mut cred_file, config, false)
+            .without_writing()
+            .with_cache(&mut cache)
+            .assume(profile)
+    });
+    match result {
+        Ok(cred) => println!("{}", credential_process_document(&cred)),
+        Err(e) => {
+            eprintln!("awscredx: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+/// Runs the command with the credentials of the profile in its environment.
+/// On Unix the command replaces this process, so its exit code and signals reach the caller.
+pub fn exec(profile: &str, no_store: bool, command: &[&str], config: &Config) {
+    let result = CredentialsFile::read_default().and_then(|mut cred_file| {
+        let mut assumer = RoleAssumer::new(&mut cred_file, config, false);
+        if no_store {
+            assumer = assumer.without_writing();
+        }
+        assumer.assume(profile)
+    });
+    let cred = match result {
+        Ok(cred) => cred,
+        Err(e) => {
+            eprintln!("{}: {}", util::styled_error_word(), e);
+            process::exit(1);
+        }
+    };
+
+    let region = config.region_of(&ProfileName::new(profile)).name();
+    let error = exec_command(command_with_credentials(command, &cred, region));
+    eprintln!(
+        "{}: cannot run {}: {}",
+        util::styled_error_word(),
+        command[0],
+        error
+    );
+    process::exit(127);
+}
+
+fn command_with_credentials(
+    command: &[&str],
+    cred: &AwsCredentials,
+    region: &str,
+) -> process::Command {
+    let mut cmd = process::Command::new(command[0]);
+    cmd.args(&command[1..])
+        .env_remove("AWS_PROFILE")
+        .env("AWS_ACCESS_KEY_ID", cred.aws_access_key_id())
+        .env("AWS_SECRET_ACCESS_KEY", cred.aws_secret_access_key())
+        .env("AWS_REGION", region)
+        .env("AWS_DEFAULT_REGION", region);
+    match cred.token() {
+        Some(token) => cmd.env("AWS_SESSION_TOKEN", token),
+        None => cmd.env_remove("AWS_SESSION_TOKEN"),
+    };
+    match cred.expires_at() {
+        Some(exp) => cmd.env(
+            "AWS_CREDENTIAL_EXPIRATION",
+            exp.to_rfc3339_opts(SecondsFormat::Secs, true),
+        ),
+        None => cmd.env_remove("AWS_CREDENTIAL_EXPIRATION"),
+    };
+    cmd
+}
+
+#[cfg(unix)]
+fn exec_command(mut cmd: process::Command) -> std::io::Error {
+    use std::os::unix::process::CommandExt;
+    cmd.exec()
+}
+
+#[cfg(not(unix))]
+fn exec_command(mut cmd: process::Command) -> std::io::Error {
+    match cmd.status() {
+        Ok(status) => process::exit(status.code().unwrap_or(1)),
+        Err(e) => e,
+    }
+}
+
+/// The temporary credentials of `credential-process` are kept apart from the credentials file,
+/// because the AWS tools read the keys from there and would never call awscredx again,
+/// even after the keys have expired.
+pub fn credential_process_cache() -> Result<CredentialsFile, String> {
+    let dir = util::create_storage_dir()?;
+    CredentialsFile::read(
+        dir.join("credential-process"),
+        dir.join("credential-process-expirations.toml"),
+    )
+}
+
+fn credential_process_document(cred: &AwsCredentials) -> serde_json::Value {
+    let mut doc = json!({
+        "Version": 1,
+        "AccessKeyId": cred.aws_access_key_id(),
+        "SecretAccessKey": cred.aws_secret_access_key(),
+    });
+    if let Some(token) = cred.token() {
+        doc["SessionToken"] = json!(token);
+    }
+    if let Some(exp) = cred.expires_at() {
+        doc["Expiration"] = json!(exp.to_rfc3339_opts(SecondsFormat::Secs, true));
+    }
+    doc
+}
+
 fn check_newer_version() {
     match crate::version::check_new_version() {
         Ok(Some(pv)) => eprintln!("{}", &pv),
@@ -70,22 +194,99 @@ fn check_newer_version() {
     }
 }
 
-fn print_profile(profile_name: &str, config: &Config) {
-    match env::var_os(SHELL_VAR) {
-        Some(shell) => {
-            let file = Path::new(&shell).file_name().unwrap().to_str().unwrap();
-            match file {
-                "fish" => print_fish_profile(profile_name, config),
-                "zsh" => print_sh_profile(profile_name, config, true),
-                _ => print_sh_profile(profile_name, config, false),
-            }
-        }
-        None => print_sh_profile(profile_name, config, false),
+/// Environment variables set or, if `None`, unset by `assume` and `unassume`.
+type EnvVars = Vec<(&'static str, Option<String>)>;
+
+/// The variables are always all listed, so that nothing of a previously assumed profile remains.
+fn profile_env(
+    profile_name: &str,
+    cred: &AwsCredentials,
+    export: ExportMode,
+    config: &Config,
+) -> EnvVars {
+    let region = config.region_of(&ProfileName::new(profile_name)).name();
+    let keys = export != ExportMode::Profile;
+    let if_keys = |value: Option<String>| if keys { value } else { None };
+    vec![
+        (
+            "AWS_PROFILE",
+            Some(profile_name.to_owned()).filter(|_| export != ExportMode::Keys),
+        ),
+        (
+            "AWS_ACCESS_KEY_ID",
+            if_keys(Some(cred.aws_access_key_id().to_owned())),
+        ),
+        (
+            "AWS_SECRET_ACCESS_KEY",
+            if_keys(Some(cred.aws_secret_access_key().to_owned())),
+        ),
+        ("AWS_SESSION_TOKEN", if_keys(cred.token().clone())),
+        (
+            "AWS_CREDENTIAL_EXPIRATION",
+            if_keys(
+                cred.expires_at()
+                    .map(|exp| exp.to_rfc3339_opts(SecondsFormat::Secs, true)),
+            ),
+        ),
+        ("AWS_REGION", Some(region.to_owned())),
+        ("AWS_DEFAULT_REGION", Some(region.to_owned())),
+        (PROFILE_VAR, Some(profile_name.to_owned())),
+    ]
+}
+
+fn unassume_env() -> EnvVars {
+    [
+        "AWS_PROFILE",
+        "AWS_ACCESS_KEY_ID",
+        "AWS_SECRET_ACCESS_KEY",
+        "AWS_SESSION_TOKEN",
+        "AWS_CREDENTIAL_EXPIRATION",
+        "AWS_REGION",
+        "AWS_DEFAULT_REGION",
+        PROFILE_VAR,
+    ]
+    .iter()
+    .map(|name| (*name, None))
+    .collect()
+}
+
+/// Prints shell commands that clear the variables set by `assume`.
+pub fn unassume() {
+    let vars = unassume_env();
+    match shell_name().as_deref() {
+        Some("fish") => print_fish_env(&vars),
+        Some("nu") => print_nu_env(&vars, false),
+        Some("pwsh") => print_pwsh_env(&vars),
+        Some("elvish") => print_elvish_env(&vars),
+        Some("xonsh") => print_xonsh_env(&vars),
+        _ => print_sh_env(&vars),
     }
 }
 
-fn print_fish_profile(profile_name: &str, config: &Config) {
-    println!("set -xg AWS_PROFILE {}; ", profile_name);
+fn shell_name() -> Option<String> {
+    env::var_os(SHELL_VAR).map(|shell| {
+        Path::new(&shell)
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .into_owned()
+    })
+}
+
+fn print_profile(vars: &EnvVars, config: &Config) {
+    match shell_name().as_deref() {
+        Some("fish") => print_fish_profile(vars, config),
+        Some("zsh") => print_sh_profile(vars, config, true),
+        Some("nu") => print_nu_env(vars, config.modify_shell_prompt),
+        Some("pwsh") => print_pwsh_profile(vars, config),
+        Some("elvish") => print_elvish_profile(vars, config),
+        Some("xonsh") => print_xonsh_profile(vars, config),
+        _ => print_sh_profile(vars, config, false),
+    }
+}
+
+fn print_fish_profile(vars: &EnvVars, config: &Config) {
+    print_fish_env(vars);
     if config.modify_shell_prompt {
         println!(
             r#"function fish_prompt
@@ -101,8 +302,122 @@ end
     }
 }
 
-fn print_sh_profile(profile_name: &str, config: &Config, zsh: bool) {
-    println!("export AWS_PROFILE={}; ", profile_name);
+fn print_fish_env(vars: &EnvVars) {
+    for (name, value) in vars {
+        match value {
+            Some(v) => println!("set -xg {} {}; ", name, fish_quote(v)),
+            None => println!("set -e {}; ", name),
+        }
+    }
+}
+
+fn fish_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+/// Prints a record for the `assume` command of the Nushell init script:
+/// `set` is passed to `load-env`, `unset` to `hide-env`.
+fn print_nu_env(vars: &EnvVars, prompt: bool) {
+    println!("{}", nu_record(vars, prompt));
+}
+
+fn nu_record(vars: &EnvVars, prompt: bool) -> String {
+    let set: Vec<String> = vars
+        .iter()
+        .filter_map(|(name, value)| {
+            let v = value.as_ref()?;
+            Some(format!("{}: {}", name, json_quote(v)))
+        })
+        .collect();
+    let unset: Vec<&str> = vars
+        .iter()
+        .filter(|(_, value)| value.is_none())
+        .map(|(name, _)| *name)
+        .collect();
+    format!(
+        "{{set: {{{}}}, unset: [{}], prompt: {}}}",
+        set.join(", "),
+        unset.join(", "),
+        prompt
+    )
+}
+
+/// Nushell and Python double quoted strings take the same escapes as JSON.
+fn json_quote(value: &str) -> String {
+    serde_json::to_string(value).expect("string serializes")
+}
+
+fn print_pwsh_profile(vars: &EnvVars, config: &Config) {
+    print_pwsh_env(vars);
+    if config.modify_shell_prompt {
+        println!(
+            r#"function global:prompt {{
+  "$(__awscredx_prompt) " + (& $global:_AWSCREDX_ORIGINAL_PROMPT)
+}}"#
+        )
+    }
+}
+
+fn print_pwsh_env(vars: &EnvVars) {
+    for (name, value) in vars {
+        match value {
+            Some(v) => println!("$env:{} = {}; ", name, pwsh_quote(v)),
+            None => println!("Remove-Item Env:{} -ErrorAction SilentlyContinue; ", name),
+        }
+    }
+}
+
+fn pwsh_quote(value: &str) -> String {
+    let mut quoted = String::from("'");
+    for c in value.chars() {
+        // PowerShell also treats typographic quotes as single quotes.
+        if matches!(c, '\'' | '\u{2018}' | '\u{2019}' | '\u{201A}' | '\u{201B}') {
+            quoted.push(c);
+        }
+        quoted.push(c);
+    }
+    quoted.push('\'');
+    quoted
+}
+
+fn print_elvish_profile(vars: &EnvVars, config: &Config) {
+    print_elvish_env(vars);
+    if config.modify_shell_prompt {
+        println!("set edit:prompt = $awscredx-prompt~")
+    }
+}
+
+fn print_elvish_env(vars: &EnvVars) {
+    for (name, value) in vars {
+        match value {
+            Some(v) => println!("set-env {} {}", name, elvish_quote(v)),
+            None => println!("unset-env {}", name),
+        }
+    }
+}
+
+fn elvish_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+fn print_xonsh_profile(vars: &EnvVars, config: &Config) {
+    print_xonsh_env(vars);
+    if config.modify_shell_prompt {
+        println!("$PROMPT = _awscredx_prompt")
+    }
+}
+
+fn print_xonsh_env(vars: &EnvVars) {
+    for (name, value) in vars {
+        match value {
+            Some(v) => println!("${} = {}", name, json_quote(v)),
+            None => println!("${{...}}.pop('{}', None)", name),
+        }
+    }
+}
+
+fn print_sh_profile(vars: &EnvVars, config: &Config, zsh: bool) {
+    print_sh_env(vars);
     if config.modify_shell_prompt {
         if zsh {
             println!("setopt PROMPT_SUBST");
@@ -111,6 +426,19 @@ fn print_sh_profile(profile_name: &str, config: &Config, zsh: bool) {
     }
 }
 
+fn print_sh_env(vars: &EnvVars) {
+    for (name, value) in vars {
+        match value {
+            Some(v) => println!("export {}={}; ", name, sh_quote(v)),
+            None => println!("unset {}; ", name),
+        }
+    }
+}
+
+fn sh_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
 fn get_https_connector() -> Result<ProxyConnector<HttpsConnector<HttpConnector>>, String> {
     let connector = HttpsConnector::new();
     Ok(match util::get_https_proxy() {
@@ -125,7 +453,7 @@ fn get_https_connector() -> Result<ProxyConnector<HttpsConnector<HttpConnector>>
     })
 }
 
-fn outdated_script() -> bool {
+pub fn outdated_script() -> bool {
     env::var("AWSCREDX_SCRIPT_VERSION").is_ok()
 }
 
@@ -135,16 +463,12 @@ fn print_update_instructions() {
 {}
 This new version introduces a breaking change in the script initialization.
 Please replace the line {} in your init script with
- - {} for bash,
- - {} for zsh,
- - {} for fish,
+{}
 Then open a new console window and you can assume a role as before with {}
 "#,
         styles::number().paint("ATTENTION!!!"),
         styles::path().paint("source ~/.local/share/awscredx/script.sh"),
-        styles::path().paint("eval $(awscredx init bash)"),
-        styles::path().paint("eval $(awscredx init zsh)"),
-        styles::path().paint("awscredx init fish | source"),
+        init::init_instructions(" "),
         styles::number().paint("assume <profile-name-in-your-config.toml>"),
     );
 }
@@ -155,3 +479,72 @@ fn create_runtime() -> Runtime {
         .build()
         .expect("cannot build runtime")
 }
+
+#[test]
+fn credential_process_json() {
+    use chrono::TimeZone;
+
+    let cred = AwsCredentials::new(
+        "AK",
+        "SK",
+        Some("ST".to_owned()),
+        Some(Utc.ymd(2030, 1, 2).and_hms(3, 4, 5)),
+    );
+    assert_eq!(
+        credential_process_document(&cred).to_string(),
+        r#"{"AccessKeyId":"AK","Expiration":"2030-01-02T03:04:05Z","SecretAccessKey":"SK","SessionToken":"ST","Version":1}"#
+    );
+    let main = AwsCredentials::new("AK", "SK", None, None);
+    assert_eq!(
+        credential_process_document(&main).to_string(),
+        r#"{"AccessKeyId":"AK","SecretAccessKey":"SK","Version":1}"#
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn exec_with_credentials() {
+    use std::ffi::OsStr;
+
+    let cred = AwsCredentials::new("AK", "SK", Some("ST".to_owned()), None);
+    let script = r#"printf '%s %s %s %s' "$AWS_ACCESS_KEY_ID" "$AWS_SECRET_ACCESS_KEY" "$AWS_SESSION_TOKEN" "$AWS_REGION"; exit 3"#;
+    let mut cmd = command_with_credentials(&["sh", "-c", script], &cred, "eu-west-1");
+    let removed: Vec<&OsStr> = cmd
+        .get_envs()
+        .filter(|(_, value)| value.is_none())
+        .map(|(name, _)| name)
+        .collect();
+    assert_eq!(
+        removed,
+        vec![
+            OsStr::new("AWS_CREDENTIAL_EXPIRATION"),
+            OsStr::new("AWS_PROFILE")
+        ]
+    );
+
+    let output = cmd.output().unwrap();
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "AK SK ST eu-west-1"
+    );
+    assert_eq!(output.status.code(), Some(3));
+}
+
+#[test]
+fn quote_shell_values() {
+    assert_eq!(sh_quote("it's"), r"'it'\''s'");
+    assert_eq!(sh_quote("$HOME `x`"), "'$HOME `x`'");
+    assert_eq!(fish_quote(r"it's a\b"), r"'it\'s a\\b'");
+    assert_eq!(pwsh_quote("it's $HOME"), "'it''s $HOME'");
+    assert_eq!(pwsh_quote("\u{2019}"), "'\u{2019}\u{2019}'");
+    assert_eq!(elvish_quote("it's $HOME"), "'it''s $HOME'");
+    assert_eq!(json_quote(r#"say "hi" \o/"#), r#""say \"hi\" \\o/""#);
+    let vars: EnvVars = vec![
+        ("AWS_PROFILE", Some("dev".to_owned())),
+        ("AWS_REGION", None),
+    ];
+    assert_eq!(
+        nu_record(&vars, true),
+        r#"{set: {AWS_PROFILE: "dev"}, unset: [AWS_REGION], prompt: true}"#
+    );
+}
@@ -0,0 +1,449 @@
+use std::fs;
+use std::path::PathBuf;
+use std::thread;
+
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use reqwest::blocking::Client;
+use reqwest::StatusCode;
+use rusoto_credential::AwsCredentials;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::config::SsoConfig;
+use crate::{styles, util};
+
+const CLIENT_NAME: &str = "awscredx";
+const DEVICE_CODE_GRANT_TYPE: &str = "urn:ietf:params:oauth:grant-type:device_code";
+
+/// Client registration and access token of the OIDC device authorization,
+/// cached in the storage directory between runs.
+#[derive(Serialize, Deserialize)]
+struct TokenCache {
+    start_url: String,
+    #[serde(default)]
+    region: String,
+    client_id: String,
+    client_secret: String,
+    client_secret_expires_at: DateTime<Utc>,
+    access_token: Option<String>,
+    access_token_expires_at: Option<DateTime<Utc>>,
+}
+
+impl TokenCache {
+    /// Client registrations and access tokens are only valid for the portal they were issued for.
+    fn belongs_to(&self, config: &SsoConfig) -> bool {
+        self.start_url == config.start_url && self.region == config.region_name()
+    }
+
+    fn valid_access_token(&self, config: &SsoConfig) -> Option<&str> {
+        if !self.belongs_to(config) {
+            return None;
+        }
+        match (&self.access_token, &self.access_token_expires_at) {
+            (Some(token), Some(exp)) if *exp - Utc::now() > Duration::minutes(1) => Some(token),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RegisterClientResponse {
+    client_id: String,
+    client_secret: String,
+    client_secret_expires_at: i64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DeviceAuthorizationResponse {
+    device_code: String,
+    user_code: String,
+    verification_uri: String,
+    verification_uri_complete: Option<String>,
+    expires_in: i64,
+    interval: Option<u64>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CreateTokenResponse {
+    access_token: String,
+    expires_in: i64,
+}
+
+#[derive(Deserialize)]
+struct OidcError {
+    error: String,
+    error_description: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GetRoleCredentialsResponse {
+    role_credentials: RoleCredentials,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RoleCredentials {
+    access_key_id: String,
+    secret_access_key: String,
+    session_token: String,
+    /// Milliseconds since epoch
+    expiration: i64,
+}
+
+pub fn token_cache_path() -> Result<PathBuf, String> {
+    Ok(util::storage_dir()?.join("sso-token.json"))
+}
+
+pub struct SsoClient<'a> {
+    config: &'a SsoConfig,
+    cache_path: PathBuf,
+    http: Client,
+}
+
+impl<'a> SsoClient<'a> {
+    pub fn new(config: &'a SsoConfig) -> Result<Self, String> {
+        Self::with_cache(config, token_cache_path()?)
+    }
+
+    fn with_cache(config: &'a SsoConfig, cache_path: PathBuf) -> Result<Self, String> {
+        Ok(Self {
+            config,
+            cache_path,
+            http: util::get_https_client()?,
+        })
+    }
+
+    /// Returns the role credentials, logging in via the browser if there is no valid access token.
+    pub fn role_credentials(
+        &self,
+        account_id: &str,
+        role_name: &str,
+    ) -> Result<AwsCredentials, String> {
+        let mut cache = self.read_cache();
+        let cached_token = cache
+            .as_ref()
+            .and_then(|c| c.valid_access_token(self.config))
+            .map(str::to_owned);
+        if let Some(token) = cached_token {
+            if let Some(cred) = self.get_role_credentials(&token, account_id, role_name)? {
+                return Ok(cred);
+            }
+        }
+        let token = self.login(&mut cache)?;
+        self.get_role_credentials(&token, account_id, role_name)?
+            .ok_or(format!(
+                "SSO access token was rejected for account {} and role {}",
+                account_id, role_name
+            ))
+    }
+
+    /// Returns `None` if the access token has been rejected.
+    fn get_role_credentials(
+        &self,
+        token: &str,
+        account_id: &str,
+        role_name: &str,
+    ) -> Result<Option<AwsCredentials>, String> {
+        let response = self
+            .http
+            .get(format!(
+                "{}/federation/credentials",
+                self.config.portal_endpoint()
+            ))
+            .query(&[("account_id", account_id), ("role_name", role_name)])
+            .header("x-amz-sso_bearer_token", token)
+            .send()
+            .map_err(|e| format!("cannot get SSO role credentials: {}", e))?;
+        if response.status() == StatusCode::UNAUTHORIZED {
+            return Ok(None);
+        }
+        if !response.status().is_success() {
+            return Err(format!(
+                "cannot get SSO role credentials for account {} and role {}: {} {}",
+                account_id,
+                role_name,
+                response.status(),
+                response.text().unwrap_or_default()
+            ));
+        }
+        let rc = response
+            .json::<GetRoleCredentialsResponse>()
+            .map_err(|e| format!("cannot decode SSO role credentials: {}", e))?
+            .role_credentials;
+        Ok(Some(AwsCredentials::new(
+            rc.access_key_id,
+            rc.secret_access_key,
+            Some(rc.session_token),
+            Some(Utc.timestamp_millis_opt(rc.expiration).unwrap()),
+        )))
+    }
+
+    /// Runs the OIDC device authorization flow and caches the new access token.
+    fn login(&self, cache: &mut Option<TokenCache>) -> Result<String, String> {
+        let reusable = cache.as_ref().is_some_and(|c| {
+            c.belongs_to(self.config)
+                && c.client_secret_expires_at - Utc::now() > Duration::hours(1)
+        });
+        if !reusable {
+            *cache = Some(self.register_client()?);
+        }
+        let c = cache.as_mut().expect("registered client");
+
+        let auth: DeviceAuthorizationResponse = self.post_oidc(
+            "device_authorization",
+            json!({
+                "clientId": &c.client_id,
+                "clientSecret": &c.client_secret,
+                "startUrl": &self.config.start_url,
+            }),
+        )?;
+        let url = auth
+            .verification_uri_complete
+            .as_deref()
+            .unwrap_or(&auth.verification_uri);
+        eprintln!(
+            "Confirm the code {} to sign in to AWS SSO at\n  {}",
+            styles::number().paint(&auth.user_code),
+            styles::path().paint(url)
+        );
+        if self.config.open_browser {
+            let _ = webbrowser::open(url);
+        }
+
+        let deadline = Utc::now() + Duration::seconds(auth.expires_in);
+        let mut interval = auth.interval.unwrap_or(5);
+        let token = loop {
+            let response = self
+                .http
+                .post(format!("{}/token", self.config.oidc_endpoint()))
+                .json(&json!({
+                    "clientId": &c.client_id,
+                    "clientSecret": &c.client_secret,
+                    "grantType": DEVICE_CODE_GRANT_TYPE,
+                    "deviceCode": &auth.device_code,
+                }))
+                .send()
+                .map_err(|e| format!("cannot create SSO token: {}", e))?;
+            if response.status().is_success() {
+                break response
+                    .json::<CreateTokenResponse>()
+                    .map_err(|e| format!("cannot decode SSO token: {}", e))?;
+            }
+            let err: OidcError = response
+                .json()
+                .map_err(|e| format!("cannot decode SSO token error: {}", e))?;
+            match err.error.as_str() {
+                "authorization_pending" => {}
+                "slow_down" => interval += 5,
+                _ => {
+                    return Err(format!(
+                        "SSO login failed: {} {}",
+                        err.error,
+                        err.error_description.unwrap_or_default()
+                    ))
+                }
+            }
+            if Utc::now() > deadline {
+                return Err("SSO login timed out".to_owned());
+            }
+            thread::sleep(std::time::Duration::from_secs(interval));
+        };
+
+        c.access_token = Some(token.access_token.clone());
+        c.access_token_expires_at = Some(Utc::now() + Duration::seconds(token.expires_in));
+        self.write_cache(c)?;
+        Ok(token.access_token)
+    }
+
+    fn register_client(&self) -> Result<TokenCache, String> {
+        let r: RegisterClientResponse = self.post_oidc(
+            "client/register",
+            json!({
+                "clientName": CLIENT_NAME,
+                "clientType": "public",
+            }),
+        )?;
+        Ok(TokenCache {
+            start_url: self.config.start_url.clone(),
+            region: self.config.region_name().to_owned(),
+            client_id: r.client_id,
+            client_secret: r.client_secret,
+            client_secret_expires_at: Utc.timestamp_opt(r.client_secret_expires_at, 0).unwrap(),
+            access_token: None,
+            access_token_expires_at: None,
+        })
+    }
+
+    fn post_oidc<T: serde::de::DeserializeOwned>(
+        &self,
+        path: &str,
+        body: serde_json::Value,
+    ) -> Result<T, String> {
+        let response = self
+            .http
+            .post(format!("{}/{}", self.config.oidc_endpoint(), path))
+            .json(&body)
+            .send()
+            .map_err(|e| format!("SSO OIDC request {} failed: {}", path, e))?;
+        if !response.status().is_success() {
+            return Err(format!(
+                "SSO OIDC request {} failed: {} {}",
+                path,
+                response.status(),
+                response.text().unwrap_or_default()
+            ));
+        }
+        response
+            .json()
+            .map_err(|e| format!("cannot decode SSO OIDC response of {}: {}", path, e))
+    }
+
+    fn read_cache(&self) -> Option<TokenCache> {
+        fs::read_to_string(&self.cache_path)
+            .ok()
+            .and_then(|c| serde_json::from_str(&c).ok())
+    }
+
+    fn write_cache(&self, cache: &TokenCache) -> Result<(), String> {
+        if let Some(dir) = self.cache_path.parent() {
+            if !dir.as_os_str().is_empty() && !dir.exists() {
+                util::create_storage_dir()?;
+            }
+        }
+        let content = serde_json::to_string(cache).expect("encoded SSO token cache");
+        util::write_atomically(&self.cache_path, &content, 0o600)
+    }
+}
+
+#[cfg(test)]
+mod stand_in {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::thread::{self, JoinHandle};
+
+    /// Serves one canned response per expected request and returns the received requests
+    /// as "METHOD path\nheaders\nbody".
+    pub fn serve(responses: Vec<(u16, String)>) -> (String, JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let handle = thread::spawn(move || {
+            let mut requests = Vec::new();
+            for (status, body) in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request = String::new();
+                let mut content_length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line == "\r\n" {
+                        break;
+                    }
+                    if let Some(v) = line.to_lowercase().strip_prefix("content-length:") {
+                        content_length = v.trim().parse().unwrap();
+                    }
+                    request.push_str(&line);
+                }
+                let mut req_body = vec![0; content_length];
+                reader.read_exact(&mut req_body).unwrap();
+                request.push_str(&String::from_utf8(req_body).unwrap());
+                requests.push(request);
+
+                write!(
+                    stream,
+                    "HTTP/1.1 {} X\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                )
+                .unwrap();
+            }
+            requests
+        });
+        (url, handle)
+    }
+}
+
+#[test]
+fn login_and_get_role_credentials() {
+    const CACHE_PATH: &str = "./test.sso-token.json";
+    let expiration = (Utc::now() + Duration::hours(1)).timestamp_millis();
+    let credentials = format!(
+        r#"{{"roleCredentials":{{"accessKeyId":"AK","secretAccessKey":"SK","sessionToken":"ST","expiration":{}}}}}"#,
+        expiration
+    );
+    let (url, server) = stand_in::serve(vec![
+        (
+            200,
+            format!(
+                r#"{{"clientId":"cid","clientSecret":"csecret","clientSecretExpiresAt":{}}}"#,
+                (Utc::now() + Duration::days(90)).timestamp()
+            ),
+        ),
+        (
+            200,
+            r#"{"deviceCode":"dc","userCode":"ABCD-EFGH","verificationUri":"https://device","expiresIn":600,"interval":0}"#.to_owned(),
+        ),
+        (400, r#"{"error":"authorization_pending"}"#.to_owned()),
+        (200, r#"{"accessToken":"at","expiresIn":28800}"#.to_owned()),
+        (200, credentials.clone()),
+        (200, credentials),
+    ]);
+    let config = SsoConfig {
+        start_url: "https://example.awsapps.com/start".to_owned(),
+        region: None,
+        oidc_endpoint: Some(url.clone()),
+        portal_endpoint: Some(url),
+        open_browser: false,
+    };
+    let client = SsoClient::with_cache(&config, PathBuf::from(CACHE_PATH)).unwrap();
+
+    let cred = client.role_credentials("123456789012", "Admin").unwrap();
+    assert_eq!(cred.aws_access_key_id(), "AK");
+    assert_eq!(cred.token().as_deref(), Some("ST"));
+    assert_eq!(cred.expires_at().unwrap().timestamp_millis(), expiration);
+
+    // the second call uses the cached access token
+    client.role_credentials("123456789012", "Admin").unwrap();
+
+    let requests = server.join().unwrap();
+    fs::remove_file(CACHE_PATH).unwrap();
+    assert!(requests[0].starts_with("POST /client/register "));
+    assert!(requests[1].starts_with("POST /device_authorization "));
+    assert!(requests[1].contains(r#""startUrl":"https://example.awsapps.com/start""#));
+    assert!(requests[3].contains(r#""deviceCode":"dc""#));
+    assert!(requests[4]
+        .starts_with("GET /federation/credentials?account_id=123456789012&role_name=Admin "));
+    assert!(requests[5].contains("x-amz-sso_bearer_token: at"));
+}
+
+#[test]
+fn access_token_of_other_portal() {
+    let config = SsoConfig {
+        start_url: "https://new.awsapps.com/start".to_owned(),
+        region: None,
+        oidc_endpoint: None,
+        portal_endpoint: None,
+        open_browser: false,
+    };
+    let mut cache = TokenCache {
+        start_url: "https://old.awsapps.com/start".to_owned(),
+        region: config.region_name().to_owned(),
+        client_id: "cid".to_owned(),
+        client_secret: "csecret".to_owned(),
+        client_secret_expires_at: Utc::now() + Duration::days(90),
+        access_token: Some("at".to_owned()),
+        access_token_expires_at: Some(Utc::now() + Duration::hours(8)),
+    };
+    assert_eq!(cache.valid_access_token(&config), None);
+
+    cache.start_url = config.start_url.clone();
+    assert_eq!(cache.valid_access_token(&config), Some("at"));
+
+    cache.region = "us-east-1".to_owned();
+    assert_eq!(cache.valid_access_token(&config), None);
+}
@@ -0,0 +1,149 @@
+use std::process;
+
+use chrono::{DateTime, Local, SecondsFormat, Utc};
+use rusoto_sts::{GetCallerIdentityRequest, Sts};
+use serde::Serialize;
+
+use super::assumer::create_sts_client;
+use crate::config::Config;
+use crate::credentials::{CredentialsFile, ProfileName};
+use crate::{styles, util};
+
+#[derive(Serialize)]
+struct Identity {
+    profile: String,
+    account: String,
+    arn: String,
+    user_id: String,
+    /// From the root profile down to the profile itself.
+    chain: Vec<String>,
+    expires_at: Option<String>,
+    remaining_seconds: Option<i64>,
+}
+
+/// Prints the identity behind the cached credentials of the profile.
+pub fn run(profile: Option<&str>, json: bool, config: &Config) {
+    if let Err(e) = whoami(profile, json, config) {
+        eprintln!("{}: {}", &util::styled_error_word(), e);
+        process::exit(1);
+    }
+}
+
+fn whoami(profile: Option<&str>, json: bool, config: &Config) -> Result<(), String> {
+    let profile = profile
+        .map(str::to_owned)
+        .or_else(util::current_profile)
+        .ok_or("no profile given and AWS_PROFILE is not set")?;
+    let pn = ProfileName::new(&profile);
+    let cred_file = CredentialsFile::read_default()?;
+    let cred = cred_file.get_credentials(&pn).ok_or(format!(
+        "there are no valid credentials for profile '{}', run 'assume {}' first",
+        profile, profile
+    ))?;
+    let expires_at = *cred.expires_at();
+
+    let client = create_sts_client(Some(cred.clone()), config.region_of(&pn).clone())?;
+    let runtime = super::create_runtime();
+    let caller = runtime.block_on(async move {
+        client
+            .get_caller_identity(GetCallerIdentityRequest {})
+            .await
+            .map_err(|e| format!("cannot get caller identity: {}", e))
+    })?;
+
+    let identity = Identity {
+        profile,
+        account: caller.account.unwrap_or_default(),
+        arn: caller.arn.unwrap_or_default(),
+        user_id: caller.user_id.unwrap_or_default(),
+        chain: chain(&pn, config),
+        expires_at: expires_at.map(|exp| exp.to_rfc3339_opts(SecondsFormat::Secs, true)),
+        remaining_seconds: expires_at.map(|exp| (exp - Utc::now()).num_seconds()),
+    };
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&identity).expect("encoded identity")
+        );
+    } else {
+        print_identity(&identity, expires_at);
+    }
+    Ok(())
+}
+
+fn chain(profile: &ProfileName, config: &Config) -> Vec<String> {
+    let mut chain = vec![profile.to_string()];
+    let mut current = profile;
+    while let Some(parent) = config.parent_profile(current) {
+        if chain.iter().any(|p| p == parent.as_ref()) {
+            break;
+        }
+        chain.push(parent.to_string());
+        current = parent;
+    }
+    chain.reverse();
+    chain
+}
+
+fn print_identity(identity: &Identity, expires_at: Option<DateTime<Utc>>) {
+    let row = |name: &str, value: &str| println!("{:10}{}", name, value);
+    row("Profile", &identity.profile);
+    row("Account", &identity.account);
+    row("ARN", &identity.arn);
+    row("User ID", &identity.user_id);
+    row("Chain", &identity.chain.join(" -> "));
+    match expires_at {
+        Some(exp) => {
+            let local: DateTime<Local> = exp.into();
+            row(
+                "Expires",
+                &format!(
+                    "at {} in {}",
+                    local.format("%H:%M"),
+                    styles::number().paint(util::format_duration(exp - Utc::now()))
+                ),
+            )
+        }
+        None => row("Expires", "never"),
+    }
+}
+
+#[test]
+fn chain_follows_parents() {
+    use std::fs;
+    use std::path::Path;
+
+    const CONFIG_PATH: &str = "./test.whoami.config";
+    fs::write(
+        CONFIG_PATH,
+        "mfa_serial_number = 'mfa'\n[profiles]\ndev = 'arn:aws:iam::1:role/Dev'\n\
+         child = { role_arn = 'arn:aws:iam::2:role/Child', parent_profile = 'dev' }\n",
+    )
+    .unwrap();
+    let config = Config::read_raw(Path::new(CONFIG_PATH));
+    fs::remove_file(CONFIG_PATH).unwrap();
+    let config = config.unwrap().unwrap();
+
+    assert_eq!(
+        chain(&ProfileName::new("child"), &config),
+        vec!["main", "main-mfa", "dev", "child"]
+    );
+    assert_eq!(chain(&ProfileName::new("main"), &config), vec!["main"]);
+}
+
+#[test]
+fn identity_json() {
+    let identity = Identity {
+        profile: "dev".to_owned(),
+        account: "123".to_owned(),
+        arn: "arn:aws:sts::123:assumed-role/Dev/me".to_owned(),
+        user_id: "AROA:me".to_owned(),
+        chain: vec!["main".to_owned(), "dev".to_owned()],
+        expires_at: Some("2030-01-02T03:04:05Z".to_owned()),
+        remaining_seconds: Some(42),
+    };
+    assert_eq!(
+        serde_json::to_string(&identity).unwrap(),
+        r#"{"profile":"dev","account":"123","arn":"arn:aws:sts::123:assumed-role/Dev/me","user_id":"AROA:me","chain":["main","dev"],"expires_at":"2030-01-02T03:04:05Z","remaining_seconds":42}"#
+    );
+}
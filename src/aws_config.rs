@@ -0,0 +1,186 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::Config;
+use crate::credentials::ProfileName;
+use crate::ini::Ini;
+use crate::util::{self, FileLock};
+
+/// Marks the sections in ~/.aws/config that are maintained by awscredx.
+/// Sections without the marker belong to the user and are never modified.
+const MANAGED_MARKER: &str = "# managed by awscredx";
+
+pub struct AwsConfigFile {
+    path: PathBuf,
+    content: String,
+    document: Ini,
+}
+
+impl AwsConfigFile {
+    pub fn read(path: PathBuf) -> Self {
+        let content = fs::read_to_string(&path).unwrap_or_default();
+        let document = Ini::parse(&content);
+        Self {
+            path,
+            content,
+            document,
+        }
+    }
+
+    /// Creates or updates a managed section for every configured profile
+    /// and removes the managed sections of profiles that do not exist anymore.
+    /// With `credential_process` the sections let the AWS tools call awscredx for credentials.
+    pub fn update(&mut self, config: &Config, credential_process: bool) {
+        let stale: Vec<String> = self
+            .document
+            .sections()
+            .filter(|s| s.contains_line(MANAGED_MARKER))
+            .map(|s| s.name().to_owned())
+            .filter(|name| !config.profiles.keys().any(|p| &section_name(p) == name))
+            .collect();
+        for name in stale {
+            self.document.remove_section(&name);
+        }
+
+        let program = env::current_exe().unwrap_or_else(|_| PathBuf::from("awscredx"));
+        for profile in config.profiles.keys() {
+            let name = section_name(profile);
+            if let Some(section) = self.document.section(&name) {
+                if !section.contains_line(MANAGED_MARKER) {
+                    continue;
+                }
+            }
+            let section = self.document.section_or_insert(&name);
+            if !section.contains_line(MANAGED_MARKER) {
+                section.insert_first_line(MANAGED_MARKER);
+            }
+            section.set("region", config.region_of(profile).name());
+            match &config.output {
+                Some(output) => section.set("output", output),
+                None => section.remove("output"),
+            }
+            if credential_process {
+                let command =
+                    credential_process_command(&program, util::config_file_override(), profile);
+                section.set("credential_process", &command);
+            } else {
+                section.remove("credential_process");
+            }
+        }
+    }
+
+    /// Writes the file if it has been changed.
+    pub fn write(&self) -> Result<(), String> {
+        let content = self.document.to_string();
+        if content == self.content {
+            return Ok(());
+        }
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)
+                .map_err(|e| format!("Cannot create directory {}: {}", dir.display(), e))?;
+        }
+        util::write_atomically(&self.path, &content, 0o600)
+    }
+}
+
+/// The AWS tools split the command like a POSIX shell does.
+fn credential_process_command(
+    program: &Path,
+    config_file: Option<&Path>,
+    profile: &ProfileName,
+) -> String {
+    let mut args = vec![quote(&program.to_string_lossy())];
+    if let Some(config_file) = config_file {
+        args.push("--config".to_owned());
+        args.push(quote(&config_file.to_string_lossy()));
+    }
+    args.push("credential-process".to_owned());
+    args.push(quote(profile.as_ref()));
+    args.join(" ")
+}
+
+fn quote(arg: &str) -> String {
+    let plain = |c: char| c.is_ascii_alphanumeric() || "-_./:=@%+,".contains(c);
+    if !arg.is_empty() && arg.chars().all(plain) {
+        arg.to_owned()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
+
+fn section_name(profile: &ProfileName) -> String {
+    if profile.as_ref() == "default" {
+        "default".to_owned()
+    } else {
+        format!("profile {}", profile)
+    }
+}
+
+/// Brings the managed sections of ~/.aws/config in line with the configured profiles.
+pub fn update(config: &Config) -> Result<(), String> {
+    let _lock = FileLock::acquire(&util::storage_dir()?.join("aws-config.lock"))?;
+    let mut file = AwsConfigFile::read(util::aws_config_file()?);
+    file.update(config, config.credential_process);
+    file.write()
+}
+
+/// Prints the ~/.aws/config sections that use awscredx as credential process for every profile.
+pub fn print_config(config: &Config) {
+    let mut file = AwsConfigFile {
+        path: PathBuf::new(),
+        content: String::new(),
+        document: Ini::default(),
+    };
+    file.update(config, true);
+    print!("{}", file.document);
+}
+
+#[test]
+fn update_managed_sections() {
+    const CONFIG_PATH: &str = "./test.aws-config.config";
+
+    fs::write(
+        CONFIG_PATH,
+        "mfa_serial_number = 'mfa'\n[profiles]\ndev = 'arn:aws:iam::1:role/Dev'\n",
+    )
+    .unwrap();
+    let config = Config::read_raw(Path::new(CONFIG_PATH));
+    fs::remove_file(CONFIG_PATH).unwrap();
+    let config = config.unwrap().unwrap();
+
+    let content = "[profile mine]\nregion = us-east-1\n\n\
+                   [profile old]\n# managed by awscredx\nregion = eu-west-1\n";
+    let mut file = AwsConfigFile {
+        path: PathBuf::new(),
+        content: content.to_owned(),
+        document: Ini::parse(content),
+    };
+    file.update(&config, true);
+    assert!(file.document.section("profile old").is_none());
+    assert_eq!(
+        file.document.section("profile mine").unwrap().get("region"),
+        Some("us-east-1")
+    );
+    let command = file
+        .document
+        .section("profile dev")
+        .and_then(|s| s.get("credential_process"))
+        .unwrap()
+        .to_owned();
+    assert!(command.ends_with(" credential-process dev"));
+
+    file.update(&config, false);
+    let dev = file.document.section("profile dev").unwrap();
+    assert!(dev.contains_line(MANAGED_MARKER));
+    assert_eq!(dev.get("credential_process"), None);
+
+    assert_eq!(
+        credential_process_command(
+            Path::new("/opt/my tools/awscredx"),
+            Some(Path::new("/home/me/it's.toml")),
+            &ProfileName::new("my prof"),
+        ),
+        "'/opt/my tools/awscredx' --config '/home/me/it'\\''s.toml' credential-process 'my prof'"
+    );
+}
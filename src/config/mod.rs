@@ -0,0 +1,946 @@
+use std::io::stdin;
+use std::path::Path;
+use std::process::Command;
+use std::str::FromStr;
+use std::{env, fs};
+
+use ansi_term::{Color, Style};
+use chrono::Duration;
+use linked_hash_map::LinkedHashMap;
+use rusoto_core::Region;
+use serde::de::{self, Deserializer};
+use serde::Deserialize;
+
+use crate::credentials::ProfileName;
+use crate::{styles, util};
+use validation::Problem;
+
+mod validation;
+
+#[cfg_attr(test, derive(Debug))]
+pub struct Config {
+    pub main_profile: ProfileName,
+    pub mfa_profile: ProfileName,
+    pub mfa_serial_number: Option<String>,
+    mfa_command: Option<String>,
+    pub profiles: LinkedHashMap<ProfileName, Profile>,
+    pub check_new_version_interval_days: Option<u32>,
+    pub modify_shell_prompt: bool,
+    pub export: ExportMode,
+    pub region: Region,
+    session_name: String,
+    pub rotate_credentials_days: Option<i64>,
+    pub update_aws_config: bool,
+    /// Adds `credential_process` to the managed sections of ~/.aws/config.
+    pub credential_process: bool,
+    pub output: Option<String>,
+    mfa_session_duration: Option<Duration>,
+    refresh_before_expiry: Duration,
+    pub sso: Option<SsoConfig>,
+    pub prompt: PromptConfig,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[cfg_attr(test, derive(Eq, PartialEq))]
+pub struct Profile {
+    pub role_arn: Option<String>,
+    /// Free text shown and searched in the profile picker.
+    pub description: Option<String>,
+    pub parent_profile: Option<ProfileName>,
+    #[serde(default, deserialize_with = "deserialize_region")]
+    pub region: Option<Region>,
+    #[serde(default, deserialize_with = "deserialize_duration")]
+    pub session_duration: Option<Duration>,
+    pub external_id: Option<String>,
+    pub source_identity: Option<String>,
+    #[serde(default)]
+    pub tags: LinkedHashMap<String, String>,
+    #[serde(default)]
+    pub transitive_tag_keys: Vec<String>,
+    #[serde(default)]
+    pub policy_arns: Vec<String>,
+    /// Inline session policy. In the configuration file it is either a JSON document
+    /// or a path to a JSON file, after loading it is always the compacted JSON document.
+    pub policy: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_duration")]
+    pub refresh_before_expiry: Option<Duration>,
+    pub sso_account_id: Option<String>,
+    pub sso_role_name: Option<String>,
+    /// File with an OIDC token, e.g. a Kubernetes service account token.
+    pub web_identity_token_file: Option<String>,
+    /// Shell command that prints an OIDC token.
+    pub web_identity_token_command: Option<String>,
+    /// Shell command that prints a base64 encoded SAML response of the identity provider.
+    pub saml_command: Option<String>,
+    /// ARN of the SAML provider in IAM.
+    pub saml_principal_arn: Option<String>,
+}
+
+/// What `assume` exports into the shell.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportMode {
+    /// Only `AWS_PROFILE`, the credentials stay in the credentials file.
+    Profile,
+    /// The access keys and session token instead of `AWS_PROFILE`.
+    Keys,
+    Both,
+}
+
+impl ExportMode {
+    /// The mode with access keys, keeping `AWS_PROFILE` only if it is configured as well.
+    pub fn with_keys(self) -> Self {
+        match self {
+            ExportMode::Profile | ExportMode::Keys => ExportMode::Keys,
+            ExportMode::Both => ExportMode::Both,
+        }
+    }
+}
+
+/// AWS IAM Identity Center (SSO) settings.
+#[derive(Deserialize, Debug)]
+pub struct SsoConfig {
+    pub start_url: String,
+    /// Defaults to the global region.
+    #[serde(default, deserialize_with = "deserialize_region")]
+    pub region: Option<Region>,
+    /// Overrides `https://oidc.<region>.amazonaws.com`.
+    pub oidc_endpoint: Option<String>,
+    /// Overrides `https://portal.sso.<region>.amazonaws.com`.
+    pub portal_endpoint: Option<String>,
+    #[serde(default = "default_true")]
+    pub open_browser: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Look of `awscredx print-prompt`.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct PromptConfig {
+    /// Template with placeholders like `{profile}` and `{remaining}`.
+    pub format: String,
+    #[serde(deserialize_with = "deserialize_style")]
+    pub profile_style: Style,
+    /// Style of the remaining time while the credentials are not about to expire.
+    #[serde(deserialize_with = "deserialize_style")]
+    pub fresh_style: Style,
+    /// Style of the remaining time within `refresh_before_expiry`.
+    #[serde(deserialize_with = "deserialize_style")]
+    pub expiring_style: Style,
+    #[serde(deserialize_with = "deserialize_style")]
+    pub expired_style: Style,
+    pub hide_when_unset: bool,
+    /// Printed instead of the format if no profile is assumed and `hide_when_unset` is false.
+    pub unset_text: String,
+    /// Names shown for account IDs.
+    pub account_aliases: LinkedHashMap<String, String>,
+}
+
+impl Default for PromptConfig {
+    fn default() -> Self {
+        Self {
+            format: "[{profile} {remaining}]".to_owned(),
+            profile_style: Style::new().fg(Color::White).bold(),
+            fresh_style: Style::new().fg(Color::Green),
+            expiring_style: Style::new().fg(Color::Yellow).bold(),
+            expired_style: Style::new().fg(Color::Red).bold(),
+            hide_when_unset: true,
+            unset_text: "[no profile]".to_owned(),
+            account_aliases: LinkedHashMap::new(),
+        }
+    }
+}
+
+fn deserialize_style<'de, D: Deserializer<'de>>(d: D) -> Result<Style, D::Error> {
+    styles::parse(&String::deserialize(d)?).map_err(de::Error::custom)
+}
+
+impl SsoConfig {
+    pub fn region_name(&self) -> &str {
+        self.region.as_ref().map_or("eu-central-1", |r| r.name())
+    }
+
+    pub fn oidc_endpoint(&self) -> String {
+        self.oidc_endpoint
+            .clone()
+            .unwrap_or_else(|| format!("https://oidc.{}.amazonaws.com", self.region_name()))
+    }
+
+    pub fn portal_endpoint(&self) -> String {
+        self.portal_endpoint
+            .clone()
+            .unwrap_or_else(|| format!("https://portal.sso.{}.amazonaws.com", self.region_name()))
+    }
+}
+
+fn deserialize_region<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Region>, D::Error> {
+    let name = String::deserialize(d)?;
+    Region::from_str(&name)
+        .map(Some)
+        .map_err(|_e| de::Error::custom(format!("Bad AWS region: {}", name)))
+}
+
+/// Accepts either a number of seconds or a string like "1h30m", "45m" or "900s".
+fn deserialize_duration<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Duration>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum DurationValue {
+        Seconds(i64),
+        Text(String),
+    }
+
+    match DurationValue::deserialize(d)? {
+        DurationValue::Seconds(s) => Ok(Some(Duration::seconds(s))),
+        DurationValue::Text(t) => parse_duration(&t).map(Some).map_err(de::Error::custom),
+    }
+}
+
+fn parse_duration(text: &str) -> Result<Duration, String> {
+    let bad_duration = || format!("Bad duration: '{}', expected e.g. \"1h30m\"", text);
+    let mut total = Duration::zero();
+    let mut number = String::new();
+    for c in text.trim().chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+        } else {
+            let n: i64 = number.parse().map_err(|_e| bad_duration())?;
+            total = total
+                + match c {
+                    'h' => Duration::hours(n),
+                    'm' => Duration::minutes(n),
+                    's' => Duration::seconds(n),
+                    _ => return Err(bad_duration()),
+                };
+            number.clear();
+        }
+    }
+    if !number.is_empty() || total.is_zero() {
+        return Err(bad_duration());
+    }
+    Ok(total)
+}
+
+const MAX_INLINE_POLICY_LENGTH: usize = 2048;
+
+pub const DEFAULT_REFRESH_BEFORE_EXPIRY_MINUTES: i64 = 10;
+const DEFAULT_MAIN_PROFILE: &str = "main";
+
+impl Profile {
+    pub fn is_sso(&self) -> bool {
+        self.sso_account_id.is_some() || self.sso_role_name.is_some()
+    }
+
+    pub fn is_web_identity(&self) -> bool {
+        self.web_identity_token_file.is_some() || self.web_identity_token_command.is_some()
+    }
+
+    pub fn is_saml(&self) -> bool {
+        self.saml_command.is_some() || self.saml_principal_arn.is_some()
+    }
+
+    /// Root profiles get their credentials without any parent profile.
+    pub fn is_root(&self) -> bool {
+        self.is_sso() || self.is_web_identity() || self.is_saml()
+    }
+
+    /// The account ID from the role ARN or the SSO account.
+    pub fn account_id(&self) -> Option<&str> {
+        self.sso_account_id
+            .as_deref()
+            .or_else(|| self.role_arn.as_deref()?.split(':').nth(4))
+    }
+
+    /// The role name from the role ARN or the SSO permission set.
+    pub fn role_name(&self) -> Option<&str> {
+        self.sso_role_name
+            .as_deref()
+            .or_else(|| self.role_arn.as_deref()?.rsplit('/').next())
+    }
+
+    /// Role ARN or SSO account and role this profile stands for.
+    pub fn target(&self) -> String {
+        match (&self.sso_account_id, &self.sso_role_name) {
+            (Some(account), Some(role)) => format!("sso:{}/{}", account, role),
+            _ => self.role_arn.clone().unwrap_or_default(),
+        }
+    }
+}
+
+/// Reads the inline session policy, which is either a JSON document or a path to a JSON file.
+/// Relative paths are resolved against the directory of the configuration file.
+fn load_policy(policy: &str, config_dir: &Path) -> Result<String, String> {
+    let json = if policy.trim_start().starts_with('{') {
+        policy.to_owned()
+    } else {
+        let path = config_dir.join(util::path_to_absolute(policy)?);
+        fs::read_to_string(&path)
+            .map_err(|e| format!("cannot read policy file {}: {}", path.display(), e))?
+    };
+    let doc: serde_json::Value =
+        serde_json::from_str(&json).map_err(|e| format!("invalid policy JSON: {}", e))?;
+    if !doc.is_object() {
+        return Err("policy JSON must be an object".to_owned());
+    }
+    let compact = doc.to_string();
+    if compact.len() > MAX_INLINE_POLICY_LENGTH {
+        return Err(format!(
+            "policy is {} characters long, but at most {} are allowed",
+            compact.len(),
+            MAX_INLINE_POLICY_LENGTH
+        ));
+    }
+    Ok(compact)
+}
+
+/// Replaces `${user}` with the name of the local user.
+fn expand_placeholders(text: &str) -> Result<String, String> {
+    expand_placeholders_with(text, local_user_name)
+}
+
+/// Replaces `${user}` with the name returned by `user`.
+fn expand_placeholders_with(
+    text: &str,
+    user: impl Fn() -> Result<String, String>,
+) -> Result<String, String> {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("${") {
+        out.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find('}')
+            .ok_or(format!("unterminated placeholder in '{}'", text))?;
+        match &rest[start + 2..start + end] {
+            "user" => out.push_str(&user()?),
+            x => return Err(format!("unknown placeholder '${{{}}}'", x)),
+        }
+        rest = &rest[start + end + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+fn local_user_name() -> Result<String, String> {
+    env::var("USER")
+        .or_else(|_| env::var("LOGNAME"))
+        .or_else(|_| env::var("USERNAME"))
+        .map_err(|_e| "cannot determine the local user name, USER is not set".to_owned())
+}
+
+/// Reads the OIDC token either from the token file or from the output of the token command.
+fn read_web_identity_token(profile: &Profile) -> Result<String, String> {
+    let token = match (
+        &profile.web_identity_token_file,
+        &profile.web_identity_token_command,
+    ) {
+        (Some(file), _) => {
+            let path = util::path_to_absolute(file)?;
+            fs::read_to_string(&path).map_err(|e| {
+                format!(
+                    "cannot read web identity token file {}: {}",
+                    path.display(),
+                    e
+                )
+            })?
+        }
+        (None, Some(cmd)) => run_credential_command(cmd, "web identity token")?,
+        (None, None) => return Err("no web identity token configured".to_owned()),
+    };
+    let token = token.trim();
+    if token.is_empty() {
+        return Err("web identity token is empty".to_owned());
+    }
+    Ok(token.to_owned())
+}
+
+/// Runs the shell command and returns its trimmed, non-empty output.
+fn run_credential_command(cmd: &str, what: &str) -> Result<String, String> {
+    let output = Command::new("sh")
+        .arg("-c")
+        .arg(cmd)
+        .output()
+        .map_err(|e| format!("cannot run {} command: {}", what, e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "{} command failed: {}",
+            what,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    let out = String::from_utf8(output.stdout).map_err(|_e| format!("{} is not UTF-8", what))?;
+    let out = out.trim();
+    if out.is_empty() {
+        return Err(format!("{} command printed nothing", what));
+    }
+    Ok(out.to_owned())
+}
+
+pub enum AssumeSubject {
+    Role {
+        role_arn: String,
+        session_name: String,
+        duration: Option<Duration>,
+        external_id: Option<String>,
+        source_identity: Option<String>,
+        tags: Vec<(String, String)>,
+        transitive_tag_keys: Vec<String>,
+        policy_arns: Vec<String>,
+        policy: Option<String>,
+    },
+    MfaSession {
+        serial_number: String,
+        token_code: String,
+        duration: Option<Duration>,
+    },
+    Sso {
+        account_id: String,
+        role_name: String,
+    },
+    WebIdentity {
+        role_arn: String,
+        session_name: String,
+        token: String,
+        duration: Option<Duration>,
+        policy_arns: Vec<String>,
+        policy: Option<String>,
+    },
+    Saml {
+        role_arn: String,
+        principal_arn: String,
+        assertion: String,
+        duration: Option<Duration>,
+        policy_arns: Vec<String>,
+        policy: Option<String>,
+    },
+}
+
+impl Config {
+    pub fn read() -> Result<Option<Config>, String> {
+        Self::read_raw(&util::config_file()?)
+    }
+
+    pub(crate) fn read_raw(path: &Path) -> Result<Option<Config>, String> {
+        let content = match fs::read_to_string(path) {
+            Ok(c) => c,
+            _ => return Ok(None),
+        };
+
+        #[derive(Deserialize, Debug)]
+        #[serde(untagged)]
+        enum ProfileValue {
+            Arn(String),
+            ProfileConfig(Box<Profile>),
+        }
+
+        #[derive(Deserialize, Debug)]
+        struct RawConfig {
+            main_profile: Option<ProfileName>,
+            mfa_profile: Option<String>,
+            mfa_serial_number: Option<String>,
+            mfa_command: Option<String>,
+            profiles: LinkedHashMap<ProfileName, ProfileValue>,
+            check_new_version_interval_days: Option<u32>,
+            modify_shell_prompt: Option<bool>,
+            export: Option<ExportMode>,
+            region: Option<String>,
+            session_name: Option<String>,
+            rotate_credentials_days: Option<i64>,
+            update_aws_config: Option<bool>,
+            credential_process: Option<bool>,
+            output: Option<String>,
+            #[serde(default, deserialize_with = "deserialize_duration")]
+            mfa_session_duration: Option<Duration>,
+            #[serde(default, deserialize_with = "deserialize_duration")]
+            refresh_before_expiry: Option<Duration>,
+            sso: Option<SsoConfig>,
+            #[serde(default)]
+            prompt: PromptConfig,
+        }
+
+        let rc: RawConfig = toml::from_str(&content)
+            .map_err(|e| format!("Cannot parse TOML file {}: {}", path.display(), e))?;
+        let main_profile = rc
+            .main_profile
+            .unwrap_or_else(|| ProfileName::new(DEFAULT_MAIN_PROFILE));
+        let mfa = rc.mfa_profile.unwrap_or(format!("{}-mfa", &main_profile));
+        let region = match rc.region {
+            Some(r) => Region::from_str(&r).map_err(|_e| format!("Bad AWS region: {}", r))?,
+            None => Region::EuCentral1,
+        };
+        let sso = rc.sso.map(|mut sso| {
+            sso.region.get_or_insert_with(|| region.clone());
+            sso
+        });
+        let mut config = Config {
+            main_profile,
+            mfa_serial_number: rc.mfa_serial_number,
+            mfa_command: rc.mfa_command,
+            mfa_profile: ProfileName::new(mfa),
+            profiles: rc
+                .profiles
+                .into_iter()
+                .map(|(name, value)| {
+                    (
+                        name,
+                        match value {
+                            ProfileValue::Arn(role_arn) => Profile {
+                                role_arn: Some(role_arn),
+                                ..Default::default()
+                            },
+                            ProfileValue::ProfileConfig(profile) => *profile,
+                        },
+                    )
+                })
+                .collect(),
+            check_new_version_interval_days: rc.check_new_version_interval_days,
+            modify_shell_prompt: rc.modify_shell_prompt.unwrap_or(true),
+            export: rc.export.unwrap_or(ExportMode::Profile),
+            region,
+            session_name: rc.session_name.unwrap_or_else(|| "awscredx".to_owned()),
+            rotate_credentials_days: rc.rotate_credentials_days,
+            update_aws_config: rc.update_aws_config.unwrap_or(false),
+            credential_process: rc.credential_process.unwrap_or(false),
+            output: rc.output,
+            mfa_session_duration: rc.mfa_session_duration,
+            refresh_before_expiry: rc
+                .refresh_before_expiry
+                .unwrap_or_else(|| Duration::minutes(DEFAULT_REFRESH_BEFORE_EXPIRY_MINUTES)),
+            sso,
+            prompt: rc.prompt,
+        };
+        let mut problems = Vec::new();
+        let config_dir = path.parent().unwrap_or_else(|| Path::new("."));
+        for (name, profile) in config.profiles.iter_mut() {
+            if let Some(policy) = &profile.policy {
+                match load_policy(policy, config_dir) {
+                    Ok(p) => profile.policy = Some(p),
+                    Err(e) => problems.push(Problem::new(Some(name), format!("policy: {}", e))),
+                }
+            }
+        }
+        problems.extend(config.validate());
+        if !problems.is_empty() {
+            return Err(validation::format_problems(path, &content, &problems));
+        }
+        Ok(Some(config))
+    }
+
+    /// Returns the profile whose credentials are used to assume the given profile.
+    /// The main profile, profiles that do not exist and profiles that get their credentials
+    /// from another source, e.g. SSO, have no parent.
+    pub fn parent_profile(&self, profile: &ProfileName) -> Option<&ProfileName> {
+        if profile == &self.mfa_profile {
+            Some(&self.main_profile)
+        } else {
+            self.profiles
+                .get(profile)
+                .filter(|x| !x.is_root())
+                .map(|x| x.parent_profile.as_ref().unwrap_or(&self.mfa_profile))
+        }
+    }
+
+    /// How long before their expiration the credentials of the profile are renewed.
+    pub fn refresh_before_expiry_of(&self, profile: &ProfileName) -> Duration {
+        self.profiles
+            .get(profile)
+            .and_then(|p| p.refresh_before_expiry)
+            .unwrap_or(self.refresh_before_expiry)
+    }
+
+    /// The profile's own region or the global one.
+    pub fn region_of(&self, profile: &ProfileName) -> &Region {
+        self.profiles
+            .get(profile)
+            .and_then(|p| p.region.as_ref())
+            .unwrap_or(&self.region)
+    }
+
+    pub fn assume_subject(&self, profile: &ProfileName) -> Result<Option<AssumeSubject>, String> {
+        let res = if profile == &self.mfa_profile {
+            let serial_number = self
+                .mfa_serial_number
+                .clone()
+                .ok_or("mfa_serial_number is not configured")?;
+            Some(AssumeSubject::MfaSession {
+                serial_number,
+                token_code: self.read_token_code()?,
+                duration: self.mfa_session_duration,
+            })
+        } else {
+            match self.profiles.get(profile) {
+                Some(Profile {
+                    sso_account_id: Some(account_id),
+                    sso_role_name: Some(role_name),
+                    ..
+                }) => Some(AssumeSubject::Sso {
+                    account_id: account_id.clone(),
+                    role_name: role_name.clone(),
+                }),
+                Some(p) if p.is_saml() => Some(AssumeSubject::Saml {
+                    role_arn: p.role_arn.clone().unwrap_or_default(),
+                    principal_arn: p.saml_principal_arn.clone().unwrap_or_default(),
+                    assertion: run_credential_command(
+                        p.saml_command.as_deref().unwrap_or_default(),
+                        "SAML",
+                    )?,
+                    duration: p.session_duration,
+                    policy_arns: p.policy_arns.clone(),
+                    policy: p.policy.clone(),
+                }),
+                Some(p) if p.is_web_identity() => Some(AssumeSubject::WebIdentity {
+                    role_arn: p.role_arn.clone().unwrap_or_default(),
+                    session_name: self.session_name.clone(),
+                    token: read_web_identity_token(p)?,
+                    duration: p.session_duration,
+                    policy_arns: p.policy_arns.clone(),
+                    policy: p.policy.clone(),
+                }),
+                Some(p) => Some(AssumeSubject::Role {
+                    role_arn: p.role_arn.clone().unwrap_or_default(),
+                    session_name: self.session_name.clone(),
+                    duration: p.session_duration,
+                    external_id: p.external_id.clone(),
+                    source_identity: p
+                        .source_identity
+                        .as_deref()
+                        .map(expand_placeholders)
+                        .transpose()?,
+                    tags: p.tags.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+                    transitive_tag_keys: p.transitive_tag_keys.clone(),
+                    policy_arns: p.policy_arns.clone(),
+                    policy: p.policy.clone(),
+                }),
+                None => None,
+            }
+        };
+        Ok(res)
+    }
+
+    fn read_token_code(&self) -> Result<String, String> {
+        match self.mfa_code_from_command() {
+            Some(code) => code,
+            None => {
+                eprint!("MFA token: ");
+                let mut s = String::with_capacity(10);
+                stdin()
+                    .read_line(&mut s)
+                    .map_err(|e| format!("cannot read MFA token: {}", e))?;
+                let trimmed = s.trim_end();
+                validate_mfa_code(trimmed)
+            }
+        }
+    }
+
+    /// Runs the configured `mfa_command`, `None` if there is none.
+    pub fn mfa_code_from_command(&self) -> Option<Result<String, String>> {
+        let cmd = self.mfa_command.as_ref()?;
+        let output = Command::new("sh")
+            .arg("-c")
+            .arg(cmd)
+            .output()
+            .expect("failed to run shell");
+        let stdout_raw = String::from_utf8(output.stdout).expect("NOT UTF-8 input");
+        let stderr_raw = String::from_utf8(output.stderr).expect("NOT UTF-8 input");
+
+        Some(if output.status.success() {
+            let trimmed = stdout_raw.trim();
+            if trimmed.is_empty() {
+                Err(format!(
+                    "cannot get MFA code\nResponse from MFA command:\n{}\n{}",
+                    &stdout_raw, &stderr_raw
+                ))
+            } else {
+                validate_mfa_code(trimmed)
+            }
+        } else {
+            Err(stdout_raw + &stderr_raw)
+        })
+    }
+}
+
+fn validate_mfa_code(code: &str) -> Result<String, String> {
+    if code.len() == 6 && code.chars().all(char::is_numeric) {
+        Ok(code.to_owned())
+    } else {
+        Err(format!("'{}' is not a valid MFA code", code))
+    }
+}
+
+#[test]
+fn parse_config() {
+    const TEST_CONFIG_PATH: &str = "./test.config";
+
+    fs::write(
+        TEST_CONFIG_PATH,
+        r#"
+    main_profile = 'abc'
+    mfa_serial_number = 'mfa2'
+
+    [profiles]
+    prof1 = 'arn1'
+    prof2 = 'arn2'
+    [profiles.prof3]
+    role_arn = "arn3"
+    parent_profile="prof2"
+    region = "us-east-1"
+    session_duration = "30m"
+    refresh_before_expiry = "15m"
+    external_id = "ext3"
+    source_identity = "${user}"
+    transitive_tag_keys = ["team"]
+    [profiles.prof3.tags]
+    team = "platform"
+    cost-center = "42"
+    [profiles.prof4]
+    role_arn = "arn4"
+    policy_arns = ["arn:aws:iam::aws:policy/ReadOnlyAccess"]
+    policy = """
+    { "Version": "2012-10-17",
+      "Statement": [{ "Effect": "Deny", "Action": "s3:*", "Resource": "*" }] }
+    """"#,
+    )
+    .unwrap();
+
+    let cfg = Config::read_raw(Path::new(TEST_CONFIG_PATH))
+        .unwrap()
+        .unwrap();
+    println!("cfg = {:?}", &cfg);
+
+    let arn_prof = |x: &str| Profile {
+        role_arn: Some(x.to_owned()),
+        ..Default::default()
+    };
+
+    assert_eq!(cfg.main_profile, ProfileName::new("abc"));
+    assert_eq!(cfg.mfa_serial_number.as_deref(), Some("mfa2"));
+    let prof1 = ProfileName::new("prof1".to_owned());
+    let prof2 = ProfileName::new("prof2".to_owned());
+    let prof3 = ProfileName::new("prof3".to_owned());
+
+    assert_eq!(cfg.profiles[&prof1], arn_prof("arn1"));
+    assert_eq!(cfg.profiles[&prof2], arn_prof("arn2"));
+    let pr = &cfg.profiles[&prof3];
+    assert_eq!(pr.role_arn.as_deref(), Some("arn3"));
+    let prof2 = ProfileName::new("prof2");
+    let real_prof2 = pr.parent_profile.as_ref().unwrap();
+    assert_eq!(real_prof2, &prof2);
+    assert_eq!(cfg.region_of(&prof3), &Region::UsEast1);
+    assert_eq!(cfg.region_of(&prof2), &Region::EuCentral1);
+    assert_eq!(pr.session_duration, Some(Duration::minutes(30)));
+    assert_eq!(cfg.refresh_before_expiry_of(&prof3), Duration::minutes(15));
+    assert_eq!(cfg.refresh_before_expiry_of(&prof2), Duration::minutes(10));
+    assert_eq!(pr.external_id.as_deref(), Some("ext3"));
+    assert_eq!(
+        pr.tags.keys().collect::<Vec<_>>(),
+        vec!["team", "cost-center"]
+    );
+    assert_eq!(pr.transitive_tag_keys, vec!["team".to_owned()]);
+    let pr = &cfg.profiles[&ProfileName::new("prof4")];
+    assert_eq!(pr.policy_arns.len(), 1);
+    assert_eq!(
+        pr.policy.as_deref(),
+        Some(
+            r#"{"Statement":[{"Action":"s3:*","Effect":"Deny","Resource":"*"}],"Version":"2012-10-17"}"#
+        )
+    );
+    fs::remove_file(TEST_CONFIG_PATH).unwrap();
+}
+
+#[test]
+fn parse_durations() {
+    assert_eq!(parse_duration("1h30m"), Ok(Duration::minutes(90)));
+    assert_eq!(parse_duration("900s"), Ok(Duration::seconds(900)));
+    assert_eq!(parse_duration("12h"), Ok(Duration::hours(12)));
+    assert!(parse_duration("90").is_err());
+    assert!(parse_duration("1d").is_err());
+    assert!(parse_duration("h").is_err());
+    assert!(parse_duration("").is_err());
+}
+
+#[test]
+fn expand_user_placeholder() {
+    let expand = |text| expand_placeholders_with(text, || Ok("jdoe".to_owned()));
+    assert_eq!(expand("${user}"), Ok("jdoe".to_owned()));
+    assert_eq!(expand("ci-${user}-x"), Ok("ci-jdoe-x".to_owned()));
+    assert_eq!(expand("plain"), Ok("plain".to_owned()));
+    assert!(expand("${host}").is_err());
+    assert!(expand("${user").is_err());
+    assert!(expand_placeholders_with("${user}", || Err("no user".to_owned())).is_err());
+}
+
+#[test]
+fn reject_invalid_policy() {
+    let dir = Path::new(".");
+    assert!(load_policy(r#"{"Version": "2012-10-17",}"#, dir).is_err());
+    assert!(load_policy("[]", dir).is_err());
+    assert!(load_policy("./does-not-exist.json", dir).is_err());
+}
+
+#[test]
+fn report_all_problems() {
+    const TEST_CONFIG_PATH: &str = "./test.invalid.config";
+
+    fs::write(
+        TEST_CONFIG_PATH,
+        r#"
+    main_profile = 'main'
+    mfa_serial_number = 'mfa'
+
+    [profiles]
+    main-mfa = 'arn0'
+    a = { role_arn = 'arn1', parent_profile = 'b' }
+    [profiles.b]
+    role_arn = "arn2"
+    parent_profile = "a"
+    [profiles.c]
+    role_arn = "arn3"
+    parent_profile = "typo"
+    [profiles.d]
+    role_arn = "arn4"
+    saml_command = "idp-login""#,
+    )
+    .unwrap();
+
+    let err = Config::read_raw(Path::new(TEST_CONFIG_PATH)).unwrap_err();
+    fs::remove_file(TEST_CONFIG_PATH).unwrap();
+    assert_eq!(
+        err,
+        r#"Invalid configuration file ./test.invalid.config:
+  ./test.invalid.config:6: profile 'main-mfa' shadows the main or MFA profile with the same name
+  ./test.invalid.config:7: parent_profile cycle: a -> b -> a
+  ./test.invalid.config:11: parent_profile 'typo' of profile 'c' does not exist
+  ./test.invalid.config:14: both saml_command and saml_principal_arn are required"#
+    );
+}
+
+#[test]
+fn reject_refresh_longer_than_session() {
+    const TEST_CONFIG_PATH: &str = "./test.refresh.config";
+
+    fs::write(
+        TEST_CONFIG_PATH,
+        r#"
+    main_profile = 'main'
+    mfa_serial_number = 'mfa'
+    refresh_before_expiry = "2h"
+    mfa_session_duration = "1h30m"
+
+    [profiles]
+    a = 'arn1'
+    [profiles.b]
+    role_arn = "arn2"
+    session_duration = "4h""#,
+    )
+    .unwrap();
+
+    let err = Config::read_raw(Path::new(TEST_CONFIG_PATH)).unwrap_err();
+    fs::remove_file(TEST_CONFIG_PATH).unwrap();
+    assert_eq!(
+        err,
+        r#"Invalid configuration file ./test.refresh.config:
+  refresh_before_expiry (7200s) must be shorter than mfa_session_duration (5400s)
+  ./test.refresh.config:8: refresh_before_expiry (7200s) must be shorter than session_duration (3600s)"#
+    );
+}
+
+#[test]
+fn read_web_identity_tokens() {
+    const TEST_TOKEN_PATH: &str = "./test.web-identity-token";
+    fs::write(TEST_TOKEN_PATH, "eyJfile\n").unwrap();
+    let from_file = Profile {
+        web_identity_token_file: Some(TEST_TOKEN_PATH.to_owned()),
+        ..Default::default()
+    };
+    let token = read_web_identity_token(&from_file);
+    fs::remove_file(TEST_TOKEN_PATH).unwrap();
+    assert_eq!(token, Ok("eyJfile".to_owned()));
+
+    let from_command = Profile {
+        web_identity_token_command: Some("echo eyJcommand".to_owned()),
+        ..Default::default()
+    };
+    assert_eq!(
+        read_web_identity_token(&from_command),
+        Ok("eyJcommand".to_owned())
+    );
+    let failing = Profile {
+        web_identity_token_command: Some("echo denied >&2; exit 1".to_owned()),
+        ..Default::default()
+    };
+    assert_eq!(
+        read_web_identity_token(&failing),
+        Err("web identity token command failed: denied".to_owned())
+    );
+}
+
+#[test]
+fn saml_profiles() {
+    const TEST_CONFIG_PATH: &str = "./test.saml.config";
+
+    fs::write(
+        TEST_CONFIG_PATH,
+        r#"
+    [profiles.corp]
+    role_arn = "arn:aws:iam::1:role/Corp"
+    saml_principal_arn = "arn:aws:iam::1:saml-provider/IdP"
+    saml_command = "echo PHNhbWw="
+    [profiles.denied]
+    role_arn = "arn:aws:iam::1:role/Corp"
+    saml_principal_arn = "arn:aws:iam::1:saml-provider/IdP"
+    saml_command = "echo denied >&2; exit 1""#,
+    )
+    .unwrap();
+    let config = Config::read_raw(Path::new(TEST_CONFIG_PATH));
+    fs::remove_file(TEST_CONFIG_PATH).unwrap();
+    let config = config.unwrap().unwrap();
+
+    let corp = ProfileName::new("corp");
+    assert!(config.profiles[&corp].is_root());
+    assert_eq!(config.parent_profile(&corp), None);
+    match config.assume_subject(&corp) {
+        Ok(Some(AssumeSubject::Saml {
+            role_arn,
+            principal_arn,
+            assertion,
+            ..
+        })) => {
+            assert_eq!(role_arn, "arn:aws:iam::1:role/Corp");
+            assert_eq!(principal_arn, "arn:aws:iam::1:saml-provider/IdP");
+            assert_eq!(assertion, "PHNhbWw=");
+        }
+        _ => panic!("expected a SAML subject"),
+    }
+    assert_eq!(
+        config
+            .assume_subject(&ProfileName::new("denied"))
+            .err()
+            .as_deref(),
+        Some("SAML command failed: denied")
+    );
+
+    fs::write(
+        TEST_CONFIG_PATH,
+        r#"
+    [profiles.a]
+    role_arn = "arn1"
+    saml_principal_arn = "arn:aws:iam::1:saml-provider/IdP"
+    saml_command = "idp-login"
+    web_identity_token_file = "token"
+    [profiles.b]
+    role_arn = "arn2"
+    saml_principal_arn = "arn:aws:iam::1:saml-provider/IdP"
+    saml_command = "idp-login"
+    parent_profile = "a""#,
+    )
+    .unwrap();
+    let err = Config::read_raw(Path::new(TEST_CONFIG_PATH)).unwrap_err();
+    fs::remove_file(TEST_CONFIG_PATH).unwrap();
+    assert_eq!(
+        err,
+        r#"Invalid configuration file ./test.saml.config:
+  ./test.saml.config:2: only one of SSO, web identity and SAML can be configured
+  ./test.saml.config:7: parent_profile cannot be combined with SSO, web identity or SAML"#
+    );
+}
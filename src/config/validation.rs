@@ -0,0 +1,295 @@
+use std::collections::HashSet;
+use std::path::Path;
+
+use chrono::Duration;
+
+use super::{expand_placeholders_with, Config};
+use crate::credentials::ProfileName;
+
+// STS limits, see https://docs.aws.amazon.com/STS/latest/APIReference/API_AssumeRole.html
+const MIN_SESSION_DURATION_SECONDS: i64 = 900;
+const MAX_ROLE_SESSION_DURATION_SECONDS: i64 = 43_200;
+const MAX_CHAINED_ROLE_SESSION_DURATION_SECONDS: i64 = 3_600;
+const MAX_MFA_SESSION_DURATION_SECONDS: i64 = 129_600;
+// STS defaults if no duration is requested
+const DEFAULT_ROLE_SESSION_DURATION_SECONDS: i64 = 3_600;
+const DEFAULT_MFA_SESSION_DURATION_SECONDS: i64 = 43_200;
+
+const MAX_POLICY_ARNS: usize = 10;
+
+/// A configuration problem, optionally related to a profile.
+pub struct Problem {
+    profile: Option<ProfileName>,
+    message: String,
+}
+
+impl Problem {
+    pub fn new(profile: Option<&ProfileName>, message: String) -> Self {
+        Self {
+            profile: profile.cloned(),
+            message,
+        }
+    }
+}
+
+impl Config {
+    /// Checks the configuration as a whole and returns all found problems.
+    pub(super) fn validate(&self) -> Vec<Problem> {
+        let mut problems = Vec::new();
+        if let Some(d) = &self.mfa_session_duration {
+            if let Err(e) =
+                check_duration("mfa_session_duration", d, MAX_MFA_SESSION_DURATION_SECONDS)
+            {
+                problems.push(Problem::new(None, e));
+            }
+        }
+        if let Err(e) = crate::prompt::check_format(&self.prompt.format) {
+            problems.push(Problem::new(None, e));
+        }
+        let mfa_session_used = self
+            .profiles
+            .keys()
+            .any(|name| self.parent_profile(name) == Some(&self.mfa_profile));
+        if mfa_session_used {
+            let duration = self
+                .mfa_session_duration
+                .unwrap_or_else(|| Duration::seconds(DEFAULT_MFA_SESSION_DURATION_SECONDS));
+            if let Err(e) = check_refresh(
+                self.refresh_before_expiry_of(&self.mfa_profile),
+                "mfa_session_duration",
+                duration,
+            ) {
+                problems.push(Problem::new(None, e));
+            }
+        }
+        for (name, profile) in self.profiles.iter() {
+            let mut problem = |message: String| problems.push(Problem::new(Some(name), message));
+
+            if name == &self.main_profile || name == &self.mfa_profile {
+                problem(format!(
+                    "profile '{}' shadows the main or MFA profile with the same name",
+                    name
+                ));
+            }
+            if profile.is_sso() {
+                if profile.sso_account_id.is_none() || profile.sso_role_name.is_none() {
+                    problem("both sso_account_id and sso_role_name are required".to_owned());
+                }
+                if self.sso.is_none() {
+                    problem("SSO profiles require the [sso] section".to_owned());
+                }
+            }
+            let sources = [
+                profile.is_sso(),
+                profile.is_web_identity(),
+                profile.is_saml(),
+            ];
+            if sources.iter().filter(|x| **x).count() > 1 {
+                problem("only one of SSO, web identity and SAML can be configured".to_owned());
+            }
+            if profile.is_saml()
+                && (profile.saml_command.is_none() || profile.saml_principal_arn.is_none())
+            {
+                problem("both saml_command and saml_principal_arn are required".to_owned());
+            }
+            if profile.web_identity_token_file.is_some()
+                && profile.web_identity_token_command.is_some()
+            {
+                problem(
+                    "web_identity_token_file and web_identity_token_command are exclusive"
+                        .to_owned(),
+                );
+            }
+            if profile.is_root() && profile.parent_profile.is_some() {
+                problem(
+                    "parent_profile cannot be combined with SSO, web identity or SAML".to_owned(),
+                );
+            }
+            if profile.is_sso() {
+                if profile.role_arn.is_some() {
+                    problem("role_arn cannot be combined with SSO".to_owned());
+                }
+            } else if profile.role_arn.is_none() {
+                problem("role_arn is required".to_owned());
+            }
+            if self.mfa_serial_number.is_none()
+                && self.parent_profile(name) == Some(&self.mfa_profile)
+            {
+                problem(format!(
+                    "mfa_serial_number is required, because profile '{}' is assumed from the MFA session",
+                    name
+                ));
+            }
+            if let Some(parent) = &profile.parent_profile {
+                if !self.profiles.contains_key(parent)
+                    && parent != &self.main_profile
+                    && parent != &self.mfa_profile
+                {
+                    problem(format!(
+                        "parent_profile '{}' of profile '{}' does not exist",
+                        parent, name
+                    ));
+                }
+            }
+            if let Some(cycle) = self.parent_cycle(name) {
+                problem(format!("parent_profile cycle: {}", cycle));
+            }
+            // the lifetime of SSO credentials is set by the permission set
+            if !profile.is_sso() {
+                let duration = profile
+                    .session_duration
+                    .unwrap_or_else(|| Duration::seconds(DEFAULT_ROLE_SESSION_DURATION_SECONDS));
+                if let Err(e) = check_refresh(
+                    self.refresh_before_expiry_of(name),
+                    "session_duration",
+                    duration,
+                ) {
+                    problem(e);
+                }
+            }
+            if let Some(d) = &profile.session_duration {
+                // AWS limits sessions of roles assumed from other roles to one hour.
+                let chained = profile
+                    .parent_profile
+                    .as_ref()
+                    .is_some_and(|p| self.profiles.contains_key(p));
+                let max = if chained {
+                    MAX_CHAINED_ROLE_SESSION_DURATION_SECONDS
+                } else {
+                    MAX_ROLE_SESSION_DURATION_SECONDS
+                };
+                if let Err(e) = check_duration("session_duration", d, max) {
+                    problem(e);
+                }
+            }
+            for key in profile
+                .transitive_tag_keys
+                .iter()
+                .filter(|k| !profile.tags.contains_key(*k))
+            {
+                problem(format!("transitive tag key '{}' is not in tags", key));
+            }
+            if profile.policy_arns.len() > MAX_POLICY_ARNS {
+                problem(format!("more than {} policy_arns", MAX_POLICY_ARNS));
+            }
+            for arn in profile
+                .policy_arns
+                .iter()
+                .filter(|a| !a.starts_with("arn:"))
+            {
+                problem(format!("policy ARN '{}' is not an ARN", arn));
+            }
+            if let Some(si) = &profile.source_identity {
+                // only the syntax, the user name is looked up when the role is assumed
+                if let Err(e) = expand_placeholders_with(si, || Ok(String::new())) {
+                    problem(format!("source_identity: {}", e));
+                }
+            }
+        }
+        problems
+    }
+
+    /// Returns the cycle, e.g. "a -> b -> a", if following the parent profiles from `start`
+    /// leads back to `start`. Only the first profile of a cycle in configuration order
+    /// reports it, so that every cycle is reported once.
+    fn parent_cycle(&self, start: &ProfileName) -> Option<String> {
+        let mut path = vec![start];
+        let mut visited = HashSet::new();
+        visited.insert(start);
+        let mut current = start;
+        while let Some(parent) = self
+            .profiles
+            .get(current)
+            .and_then(|p| p.parent_profile.as_ref())
+        {
+            if parent == start {
+                let first = self
+                    .profiles
+                    .keys()
+                    .find(|k| path.contains(k))
+                    .expect("cycle member");
+                if first != start {
+                    return None;
+                }
+                path.push(parent);
+                let names: Vec<&str> = path.iter().map(|x| x.as_ref()).collect();
+                return Some(names.join(" -> "));
+            }
+            if !visited.insert(parent) {
+                // a cycle that does not contain `start`
+                return None;
+            }
+            path.push(parent);
+            current = parent;
+        }
+        None
+    }
+}
+
+fn check_duration(what: &str, duration: &Duration, max_seconds: i64) -> Result<(), String> {
+    let seconds = duration.num_seconds();
+    if (MIN_SESSION_DURATION_SECONDS..=max_seconds).contains(&seconds) {
+        Ok(())
+    } else {
+        Err(format!(
+            "{} must be between {} and {} seconds, but is {}",
+            what, MIN_SESSION_DURATION_SECONDS, max_seconds, seconds
+        ))
+    }
+}
+
+/// Credentials that are renewed before they could ever be used would be requested on every call.
+fn check_refresh(refresh: Duration, what: &str, duration: Duration) -> Result<(), String> {
+    if refresh < duration {
+        Ok(())
+    } else {
+        Err(format!(
+            "refresh_before_expiry ({}s) must be shorter than {} ({}s)",
+            refresh.num_seconds(),
+            what,
+            duration.num_seconds()
+        ))
+    }
+}
+
+/// Formats the problems as a single error message pointing to the TOML lines of the profiles.
+pub fn format_problems(path: &Path, content: &str, problems: &[Problem]) -> String {
+    let mut out = format!("Invalid configuration file {}:", path.display());
+    for p in problems {
+        let location = p
+            .profile
+            .as_ref()
+            .and_then(|name| locate_profile(content, name.as_ref()));
+        match location {
+            Some(line) => out.push_str(&format!("\n  {}:{}: {}", path.display(), line, &p.message)),
+            None => out.push_str(&format!("\n  {}", &p.message)),
+        }
+    }
+    out
+}
+
+/// Finds the 1-based line where the profile is defined,
+/// either as `[profiles.name]` table or as `name = ...` inside `[profiles]`.
+fn locate_profile(content: &str, name: &str) -> Option<usize> {
+    let unquote = |s: &str| s.trim().trim_matches(|c| c == '"' || c == '\'').to_owned();
+    let mut in_profiles = false;
+    for (ix, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.starts_with('[') {
+            let header = line.trim_matches(|c| c == '[' || c == ']').trim();
+            if let Some(profile) = header.strip_prefix("profiles.") {
+                if unquote(profile) == name {
+                    return Some(ix + 1);
+                }
+            }
+            in_profiles = header == "profiles";
+        } else if in_profiles {
+            if let Some((key, _)) = line.split_once('=') {
+                if unquote(key) == name {
+                    return Some(ix + 1);
+                }
+            }
+        }
+    }
+    None
+}
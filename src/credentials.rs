@@ -1,8 +1,6 @@
 use std::collections::HashMap;
 use std::fmt::{Display, Error, Formatter};
-use std::fs::{self, File};
-use std::io::prelude::*;
-use std::io::BufReader;
+use std::fs;
 use std::path::{Path, PathBuf};
 use std::rc::Rc;
 
@@ -10,12 +8,14 @@ use chrono::{DateTime, Duration, Utc};
 use rusoto_credential::AwsCredentials;
 use serde::{Deserialize, Serialize};
 
-use crate::util;
+use crate::ini::{Ini, Section};
+use crate::util::{self, FileLock};
 
 #[derive(Debug)]
 pub struct CredentialsFile {
     path: PathBuf,
     expirations_path: PathBuf,
+    document: Ini,
     profiles: Vec<CredentialsProfile>,
 }
 
@@ -51,32 +51,6 @@ const ACCESS_KEY_ID: &str = "aws_access_key_id";
 const SECRET_ACCESS_KEY: &str = "aws_secret_access_key";
 const SESSION_TOKEN: &str = "aws_session_token";
 
-impl Display for CredentialsProfile {
-    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
-        if self.profile_name.as_ref().contains(' ') {
-            writeln!(f, "[\"{}\"]", &self.profile_name)?;
-        } else {
-            writeln!(f, "[{}]", &self.profile_name)?;
-        }
-        writeln!(
-            f,
-            "{} = {}",
-            ACCESS_KEY_ID,
-            self.credentials.aws_access_key_id()
-        )?;
-        writeln!(
-            f,
-            "{} = {}",
-            SECRET_ACCESS_KEY,
-            self.credentials.aws_secret_access_key()
-        )?;
-        if let Some(token) = self.credentials.token() {
-            writeln!(f, "{} = {}", SESSION_TOKEN, token)?;
-        }
-        Ok(())
-    }
-}
-
 pub struct CredentialsData<'a> {
     pub profile_name: &'a str,
     pub expires_at: &'a Option<DateTime<Utc>>,
@@ -85,88 +59,55 @@ pub struct CredentialsData<'a> {
 impl CredentialsFile {
     pub fn read<P: AsRef<Path>>(path: P, expirations_path: P) -> Result<Self, String> {
         let mut cf = Self {
-            profiles: Vec::new(),
             path: path.as_ref().to_owned(),
             expirations_path: expirations_path.as_ref().to_owned(),
+            document: Ini::default(),
+            profiles: Vec::new(),
         };
 
-        let file = match File::open(&path) {
-            Ok(f) => f,
+        let content = match fs::read_to_string(&path) {
+            Ok(c) => c,
             _ => return Ok(cf),
         };
-        let br = BufReader::new(&file);
-
-        let expiraitons = CredentialExpirations::read(&cf.expirations_path)?;
-        let mut props: HashMap<String, String> = HashMap::new();
-        let mut profile_name: Option<ProfileName> = None;
-        for line_result in br.lines() {
-            let ext = line_result.expect("Cannot read line in credentials file");
-            let line = ext.trim();
-            if line.is_empty() {
-                continue;
-            }
-            if let Some(pn) = read_profile_name(line) {
-                if let Some(prof_name) = profile_name {
-                    cf.add_credentials(prof_name, &mut props, &expiraitons)?;
-                }
-                profile_name = Some(ProfileName(Rc::new(pn.to_owned())));
-                props.clear();
-            } else if let Some(prop) = read_property(line) {
-                props.insert(prop.0.to_owned(), prop.1.to_owned());
+        cf.document = Ini::parse(&content);
+
+        let mut expirations = CredentialExpirations::read(&cf.expirations_path)?;
+        expirations.reconcile(&cf.document);
+        for section in cf.document.sections() {
+            if let Some(profile) = read_profile(section, &expirations) {
+                cf.profiles.push(profile);
             }
         }
-        if let Some(prof_name) = profile_name {
-            cf.add_credentials(prof_name, &mut props, &expiraitons)?;
-        }
 
         Ok(cf)
     }
 
-    fn add_credentials(
-        &mut self,
-        profile_name: ProfileName,
-        props: &mut HashMap<String, String>,
-        expirations: &CredentialExpirations,
-    ) -> Result<(), String> {
-        let mut get_key = |key: &str| {
-            props.remove(key).ok_or(format!(
-                "Profile {} does not have property {}",
-                &profile_name, key
-            ))
-        };
-        let key_id = get_key(ACCESS_KEY_ID)?;
-        let key_secret = get_key(SECRET_ACCESS_KEY)?;
-        let token = props.remove(SESSION_TOKEN);
-
-        let exp = expirations.0.get(&profile_name).cloned();
-        match exp {
-            Some(ex) => {
-                let now = Utc::now();
-                if now - ex > Duration::zero() {
-                    return Ok(());
-                }
-            }
-            None => {
-                if token.is_some() {
-                    return Ok(());
-                }
-            }
-        }
-
-        self.profiles.push(CredentialsProfile {
-            profile_name,
-            credentials: AwsCredentials::new(key_id, key_secret, token, exp),
-        });
+    /// Re-reads both files, e.g. after acquiring the lock,
+    /// to pick up changes made by concurrently running processes.
+    pub fn reload(&mut self) -> Result<(), String> {
+        *self = Self::read(&self.path, &self.expirations_path)?;
         Ok(())
     }
 
+    /// Locks the credentials for a read-modify-write cycle.
+    /// The lock is released when the returned value is dropped.
+    pub fn lock(&self) -> Result<FileLock, String> {
+        FileLock::acquire(&self.expirations_path.with_extension("lock"))
+    }
+
     pub fn read_default() -> Result<Self, String> {
-        let cr = util::path_to_absolute("~/.aws/credentials");
-        let ex = util::path_to_absolute(EXPIRATIONS_FILE);
-        Self::read(cr, ex)
+        Self::read(util::credentials_file()?, expirations_file()?)
     }
 
     pub fn put_credentials(&mut self, profile: ProfileName, credentials: AwsCredentials) {
+        let section = self.document.section_or_insert(profile.as_ref());
+        section.set(ACCESS_KEY_ID, credentials.aws_access_key_id());
+        section.set(SECRET_ACCESS_KEY, credentials.aws_secret_access_key());
+        match credentials.token() {
+            Some(token) => section.set(SESSION_TOKEN, token),
+            None => section.remove(SESSION_TOKEN),
+        }
+
         self.profiles.retain(|p| p.profile_name != profile);
         self.profiles.push(CredentialsProfile {
             profile_name: profile,
@@ -174,12 +115,36 @@ impl CredentialsFile {
         });
     }
 
+    /// Profiles with temporary credentials, including expired ones.
+    pub fn temporary_profiles(&self) -> Vec<ProfileName> {
+        self.document
+            .sections()
+            .filter(|s| s.get(SESSION_TOKEN).is_some())
+            .map(|s| ProfileName::new(s.name()))
+            .collect()
+    }
+
+    /// Removes the section of the profile if it holds temporary credentials.
+    /// Long-term access keys are never removed.
+    pub fn remove_temporary_credentials(&mut self, profile: &ProfileName) -> bool {
+        let temporary = self
+            .document
+            .section(profile.as_ref())
+            .is_some_and(|s| s.get(SESSION_TOKEN).is_some());
+        if temporary {
+            self.document.remove_section(profile.as_ref());
+            self.profiles.retain(|p| &p.profile_name != profile);
+        }
+        temporary
+    }
+
+    /// Writes the credentials file first and the expirations afterwards.
+    /// If the process dies in between, the new credentials are at worst considered
+    /// expired too early, but never valid for too long.
     pub fn write(&self) -> Result<(), String> {
-        let file = File::create(&self.path)
-            .map_err(|e| format!("Cannot write file {}: {}", &self.path.display(), e))?;
+        util::write_atomically(&self.path, &self.document.to_string(), 0o600)?;
         let mut expiraitons = CredentialExpirations::new();
         for profile in &self.profiles {
-            writeln!(&file, "{}", profile).expect("Cannot write credentials profile");
             if let Some(exp) = profile.credentials.expires_at() {
                 expiraitons.0.insert(profile.profile_name.clone(), *exp);
             }
@@ -194,7 +159,7 @@ impl CredentialsFile {
             .map(|p| &p.credentials)
     }
 
-    pub fn get_current_credentials_data(&self) -> impl Iterator<Item = CredentialsData> + '_ {
+    pub fn get_current_credentials_data(&self) -> impl Iterator<Item = CredentialsData<'_>> + '_ {
         self.profiles.iter().map(|x| CredentialsData {
             profile_name: &x.profile_name.0,
             expires_at: x.credentials.expires_at(),
@@ -202,26 +167,36 @@ impl CredentialsFile {
     }
 }
 
-fn read_profile_name(line: &str) -> Option<&str> {
-    if line.chars().next()? == '[' {
-        Some(line.trim_matches(|c| "[ ]\"".contains(c)))
-    } else {
-        None
+/// Reads the credentials of a section.
+/// Sections without keys as well as expired temporary credentials are skipped,
+/// they stay untouched in the document though.
+fn read_profile(
+    section: &Section,
+    expirations: &CredentialExpirations,
+) -> Option<CredentialsProfile> {
+    let key_id = section.get(ACCESS_KEY_ID)?;
+    let key_secret = section.get(SECRET_ACCESS_KEY)?;
+    let token = section.get(SESSION_TOKEN).map(str::to_owned);
+    let profile_name = ProfileName::new(section.name());
+
+    let exp = expirations.0.get(&profile_name).cloned();
+    match exp {
+        Some(ex) => {
+            if Utc::now() - ex > Duration::zero() {
+                return None;
+            }
+        }
+        None => {
+            if token.is_some() {
+                return None;
+            }
+        }
     }
-}
 
-struct Property<'a>(&'a str, &'a str);
-
-fn read_property(line: &str) -> Option<Property> {
-    let parts: Vec<&str> = line.splitn(2, '=').collect();
-    if parts.len() == 2 {
-        Some(Property(
-            parts[0].trim(),
-            parts[1].trim_matches(|c| c == ' ' || c == '"'),
-        ))
-    } else {
-        None
-    }
+    Some(CredentialsProfile {
+        profile_name,
+        credentials: AwsCredentials::new(key_id, key_secret, token, exp),
+    })
 }
 
 #[test]
@@ -309,9 +284,119 @@ fn read_credentials() {
     fs::remove_file(&cred_file.expirations_path).unwrap();
 }
 
+#[test]
+fn preserve_foreign_content() {
+    const PATH: &str = "./test.preserve";
+    const EXPIRATIONS_PATH: &str = "./test.preserve.expirations.toml";
+    let foreign = r#"# my hand-written profiles
+[default]
+aws_access_key_id = AKIADEFAULT
+aws_secret_access_key = secret
+region = eu-west-1
+
+; uses an external helper
+[helper]
+credential_process = /usr/local/bin/helper
+"#;
+    let original = format!(
+        "{}\n[assumed]\naws_access_key_id = old\naws_secret_access_key = old\naws_session_token = old\nregion = us-east-1\n",
+        foreign
+    );
+    fs::write(PATH, &original).unwrap();
+
+    let mut cred_file = CredentialsFile::read(PATH, EXPIRATIONS_PATH).unwrap();
+    cred_file.write().unwrap();
+    assert_eq!(fs::read_to_string(PATH).unwrap(), original);
+
+    cred_file.put_credentials(
+        ProfileName::new("assumed"),
+        AwsCredentials::new(
+            "new",
+            "new-secret",
+            Some("new-token".to_owned()),
+            Some(Utc::now() + Duration::minutes(10)),
+        ),
+    );
+    cred_file.put_credentials(
+        ProfileName::new("added"),
+        AwsCredentials::new("k", "s", None, None),
+    );
+    cred_file.write().unwrap();
+    assert_eq!(
+        fs::read_to_string(PATH).unwrap(),
+        format!(
+            "{}\n[assumed]\naws_access_key_id = new\naws_secret_access_key = new-secret\naws_session_token = new-token\nregion = us-east-1\n\n[added]\naws_access_key_id = k\naws_secret_access_key = s\n",
+            foreign
+        )
+    );
+
+    let cred_file = CredentialsFile::read(PATH, EXPIRATIONS_PATH).unwrap();
+    assert_eq!(
+        cred_file
+            .get_credentials(&ProfileName::new("default"))
+            .unwrap()
+            .aws_access_key_id(),
+        "AKIADEFAULT"
+    );
+    assert!(cred_file
+        .get_credentials(&ProfileName::new("helper"))
+        .is_none());
+    assert_eq!(
+        cred_file
+            .get_credentials(&ProfileName::new("assumed"))
+            .unwrap()
+            .token()
+            .as_deref(),
+        Some("new-token")
+    );
+
+    fs::remove_file(PATH).unwrap();
+    fs::remove_file(EXPIRATIONS_PATH).unwrap();
+}
+
+#[test]
+fn reconcile_expirations() {
+    const PATH: &str = "./test.reconcile";
+    const EXPIRATIONS_PATH: &str = "./test.reconcile.expirations.toml";
+    let exp = Utc::now() + Duration::minutes(10);
+    fs::write(
+        PATH,
+        "[static]\naws_access_key_id = k\naws_secret_access_key = s\n\n\
+         [temp]\naws_access_key_id = k\naws_secret_access_key = s\naws_session_token = t\n",
+    )
+    .unwrap();
+    let mut expirations = CredentialExpirations::new();
+    for name in &["static", "temp", "removed"] {
+        expirations.0.insert(ProfileName::new(name), exp);
+    }
+    expirations.write(Path::new(EXPIRATIONS_PATH)).unwrap();
+
+    let cred_file = CredentialsFile::read(PATH, EXPIRATIONS_PATH).unwrap();
+    let _lock = cred_file.lock().unwrap();
+    assert_eq!(
+        cred_file
+            .get_credentials(&ProfileName::new("static"))
+            .unwrap()
+            .expires_at(),
+        &None
+    );
+    cred_file.write().unwrap();
+    let expirations = CredentialExpirations::read(EXPIRATIONS_PATH).unwrap();
+    assert_eq!(
+        expirations.0.keys().collect::<Vec<_>>(),
+        vec![&ProfileName::new("temp")]
+    );
+
+    fs::remove_file(PATH).unwrap();
+    fs::remove_file(EXPIRATIONS_PATH).unwrap();
+    fs::remove_file("./test.reconcile.expirations.lock").unwrap();
+}
+
 pub struct CredentialExpirations(HashMap<ProfileName, DateTime<Utc>>);
 
-const EXPIRATIONS_FILE: &str = "~/.local/share/awscredx/expirations.toml";
+fn expirations_file() -> Result<PathBuf, String> {
+    Ok(util::storage_dir()?.join("expirations.toml"))
+}
 
 impl CredentialExpirations {
     fn read<P: AsRef<Path>>(path: P) -> Result<Self, String> {
@@ -325,7 +410,7 @@ impl CredentialExpirations {
     }
 
     pub fn get(profile: &str) -> Result<Option<DateTime<Utc>>, String> {
-        let mut f = Self::read(util::path_to_absolute(EXPIRATIONS_FILE))?;
+        let mut f = Self::read(expirations_file()?)?;
         Ok(f.0.remove(&ProfileName::new(profile)))
     }
 
@@ -333,11 +418,19 @@ impl CredentialExpirations {
         Self(HashMap::new())
     }
 
+    /// Drops expirations that do not belong to temporary credentials in the credentials file,
+    /// e.g. because the profile was removed or replaced by static keys.
+    fn reconcile(&mut self, document: &Ini) {
+        self.0.retain(|profile, _| {
+            document
+                .sections()
+                .any(|s| s.name() == profile.as_ref() && s.get(SESSION_TOKEN).is_some())
+        });
+    }
+
     fn write(&self, path: &Path) -> Result<(), String> {
         let content = toml::to_string(&self.0).expect("Cannot encode expirations into TOML");
-        util::create_storage_dir();
-        fs::write(path, content).map_err(|e| format!("Cannot write file: {}", e))?;
-        util::set_permissions(path, 0o600);
-        Ok(())
+        util::create_storage_dir()?;
+        util::write_atomically(path, &content, 0o600)
     }
 }
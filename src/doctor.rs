@@ -0,0 +1,359 @@
+use std::path::Path;
+use std::{env, fs, process};
+
+use ansi_term::{Color, Style};
+use chrono::{DateTime, Duration, Utc};
+
+use crate::config::Config;
+use crate::credentials::CredentialsFile;
+use crate::init::{Shell, SHELL_VAR};
+use crate::{assume, styles, util};
+
+const CLOCK_CHECK_URL: &str = "https://sts.amazonaws.com";
+const MAX_CLOCK_SKEW_MINUTES: i64 = 5;
+
+enum Status {
+    Pass,
+    Warn,
+    Fail,
+}
+
+struct Check {
+    status: Status,
+    message: String,
+    hint: Option<String>,
+}
+
+impl Check {
+    fn pass(message: impl Into<String>) -> Self {
+        Self {
+            status: Status::Pass,
+            message: message.into(),
+            hint: None,
+        }
+    }
+
+    fn warn(message: impl Into<String>, hint: impl Into<String>) -> Self {
+        Self {
+            status: Status::Warn,
+            message: message.into(),
+            hint: Some(hint.into()),
+        }
+    }
+
+    fn fail(message: impl Into<String>, hint: impl Into<String>) -> Self {
+        Self {
+            status: Status::Fail,
+            message: message.into(),
+            hint: Some(hint.into()),
+        }
+    }
+
+    fn print(&self) {
+        let label = match self.status {
+            Status::Pass => Style::new().fg(Color::Green).bold().paint("[ ok ]"),
+            Status::Warn => Style::new().fg(Color::Yellow).bold().paint("[warn]"),
+            Status::Fail => styles::failure().paint("[FAIL]"),
+        };
+        println!("{} {}", label, self.message);
+        if let Some(hint) = &self.hint {
+            println!("       {}", styles::path().paint(hint.as_str()));
+        }
+    }
+}
+
+/// Checks the whole setup and prints a report. Exits with 1 if any check failed.
+pub fn run() {
+    let mut checks = Vec::new();
+    let config = check_config(&mut checks);
+    check_credentials(config.as_ref(), &mut checks);
+    check_shell(&mut checks);
+    check_proxy(&mut checks);
+    if let Some(config) = &config {
+        check_mfa(config, &mut checks);
+    }
+    checks.push(check_clock());
+
+    for check in &checks {
+        check.print();
+    }
+    if checks.iter().any(|c| matches!(c.status, Status::Fail)) {
+        process::exit(1);
+    }
+}
+
+fn check_config(checks: &mut Vec<Check>) -> Option<Config> {
+    let path = match util::config_file() {
+        Ok(path) => path,
+        Err(e) => {
+            checks.push(Check::fail(e, "set HOME or XDG_CONFIG_HOME"));
+            return None;
+        }
+    };
+    match Config::read() {
+        Ok(Some(config)) => {
+            checks.push(Check::pass(format!(
+                "Configuration file {} is valid",
+                path.display()
+            )));
+            Some(config)
+        }
+        Ok(None) => {
+            checks.push(Check::fail(
+                format!("Configuration file {} does not exist", path.display()),
+                "run 'awscredx setup' to create it",
+            ));
+            None
+        }
+        Err(e) => {
+            checks.push(Check::fail(e, "fix the listed problems in the file"));
+            None
+        }
+    }
+}
+
+fn check_credentials(config: Option<&Config>, checks: &mut Vec<Check>) {
+    let path = match util::credentials_file() {
+        Ok(path) => path,
+        Err(e) => {
+            checks.push(Check::fail(e, "set HOME or AWS_SHARED_CREDENTIALS_FILE"));
+            return;
+        }
+    };
+    if !path.exists() {
+        checks.push(Check::fail(
+            format!("Credentials file {} does not exist", path.display()),
+            "create it with the access keys of your main profile, e.g. with 'aws configure'",
+        ));
+        return;
+    }
+    checks.push(check_permissions(&path));
+
+    let config = match config {
+        Some(c) => c,
+        None => return,
+    };
+    let cred_file = match CredentialsFile::read_default() {
+        Ok(c) => c,
+        Err(e) => {
+            checks.push(Check::fail(
+                format!("Cannot read credentials file: {}", e),
+                "check the file format",
+            ));
+            return;
+        }
+    };
+    let main_required = config.profiles.values().any(|p| !p.is_root());
+    let main = &config.main_profile;
+    if cred_file.get_credentials(main).is_some() {
+        checks.push(Check::pass(format!(
+            "Main profile '{}' has access keys",
+            main
+        )));
+    } else {
+        let message = format!(
+            "There is no section [{}] with access keys in {}",
+            main,
+            path.display()
+        );
+        let hint = "add the section or set main_profile in the configuration file";
+        checks.push(if main_required {
+            Check::fail(message, hint)
+        } else {
+            Check::warn(message, hint)
+        });
+    }
+}
+
+#[cfg(target_family = "unix")]
+fn check_permissions(path: &Path) -> Check {
+    use std::os::unix::fs::PermissionsExt;
+    match fs::metadata(path) {
+        Ok(m) if m.permissions().mode() & 0o077 == 0 => {
+            Check::pass(format!("Credentials file {} is private", path.display()))
+        }
+        Ok(m) => Check::warn(
+            format!(
+                "Credentials file {} has permissions {:o}",
+                path.display(),
+                m.permissions().mode() & 0o777
+            ),
+            format!("chmod 600 {}", path.display()),
+        ),
+        Err(e) => Check::fail(
+            format!("Cannot read {}: {}", path.display(), e),
+            "check the file permissions",
+        ),
+    }
+}
+
+#[cfg(not(target_family = "unix"))]
+fn check_permissions(path: &Path) -> Check {
+    Check::pass(format!("Credentials file {} exists", path.display()))
+}
+
+fn check_shell(checks: &mut Vec<Check>) {
+    if assume::outdated_script() {
+        checks.push(Check::fail(
+            "The shell is initialized with an outdated script (AWSCREDX_SCRIPT_VERSION is set)",
+            "replace 'source ~/.local/share/awscredx/script.sh' with the 'awscredx init' line",
+        ));
+    }
+    let shell = match env::var(SHELL_VAR) {
+        Ok(shell) => shell,
+        Err(_) => {
+            checks.push(Check::fail(
+                format!("{} is not set, the shell is not initialized", SHELL_VAR),
+                "add the line printed by 'awscredx setup' for your shell to its init script",
+            ));
+            return;
+        }
+    };
+    checks.push(Check::pass(format!("Shell '{}' is initialized", shell)));
+
+    if let Some(shell) = Shell::ALL.iter().find(|s| s.as_ref() == shell) {
+        match shell.init_file() {
+            Ok(rc) => checks.push(check_init_file(shell, &rc)),
+            Err(e) => checks.push(Check::warn(e, "set HOME")),
+        }
+    }
+}
+
+fn check_init_file(shell: &Shell, rc: &Path) -> Check {
+    match fs::read_to_string(rc) {
+        Ok(content) if content.contains("awscredx init") => {
+            Check::pass(format!("Prompt hook is in {}", rc.display()))
+        }
+        _ => Check::warn(
+            format!("{} does not initialize awscredx", rc.display()),
+            format!("add '{}' to {}", shell.init_line(), rc.display()),
+        ),
+    }
+}
+
+fn check_proxy(checks: &mut Vec<Check>) {
+    let proxy = match util::get_https_proxy() {
+        Some(p) => p,
+        None => {
+            checks.push(Check::pass("No HTTPS proxy is configured"));
+            return;
+        }
+    };
+    let valid = proxy.parse::<hyper::Uri>().is_ok() && util::get_https_client().is_ok();
+    checks.push(if valid {
+        Check::pass(format!("HTTPS proxy {}", proxy))
+    } else {
+        Check::fail(
+            format!("HTTPS proxy URL '{}' is invalid", proxy),
+            "set HTTPS_PROXY to a URL like http://proxy.example.com:8080",
+        )
+    });
+}
+
+fn check_mfa(config: &Config, checks: &mut Vec<Check>) {
+    if let Some(serial) = &config.mfa_serial_number {
+        if !(serial.starts_with("arn:aws") && serial.contains(":mfa/")) {
+            checks.push(Check::warn(
+                format!("mfa_serial_number '{}' does not look like an MFA ARN", serial),
+                "copy the ARN from IAM -> Users -> Security credentials, e.g. arn:aws:iam::123456789012:mfa/user",
+            ));
+        }
+    }
+    match config.mfa_code_from_command() {
+        Some(Ok(_)) => checks.push(Check::pass("mfa_command prints a valid MFA code")),
+        Some(Err(e)) => checks.push(Check::fail(
+            format!("mfa_command failed: {}", e.trim()),
+            "run the command in your shell and make sure it prints the 6-digit code",
+        )),
+        None => {}
+    }
+}
+
+fn check_clock() -> Check {
+    let server_time = util::get_https_client().and_then(|client| {
+        let response = client
+            .head(CLOCK_CHECK_URL)
+            .send()
+            .map_err(|e| format!("cannot reach {}: {}", CLOCK_CHECK_URL, e))?;
+        let date = response
+            .headers()
+            .get(reqwest::header::DATE)
+            .and_then(|d| d.to_str().ok())
+            .ok_or("the response has no Date header")?;
+        DateTime::parse_from_rfc2822(date)
+            .map(|d| d.with_timezone(&Utc))
+            .map_err(|e| format!("bad Date header '{}': {}", date, e))
+    });
+    match server_time {
+        Ok(time) => {
+            let skew = Utc::now() - time;
+            if skew.num_seconds().abs() < Duration::minutes(MAX_CLOCK_SKEW_MINUTES).num_seconds() {
+                Check::pass(format!("Clock skew is {}s", skew.num_seconds()))
+            } else {
+                Check::fail(
+                    format!(
+                        "Clock is off by {}s, AWS rejects such requests",
+                        skew.num_seconds()
+                    ),
+                    "synchronize your system clock, e.g. enable NTP",
+                )
+            }
+        }
+        Err(e) => Check::warn(
+            format!("Cannot check the clock: {}", e),
+            "check your network and proxy settings",
+        ),
+    }
+}
+
+#[cfg(target_family = "unix")]
+#[test]
+fn credentials_permissions() {
+    use std::os::unix::fs::PermissionsExt;
+
+    const PATH: &str = "./test.doctor.credentials";
+    fs::write(PATH, "").unwrap();
+    fs::set_permissions(PATH, fs::Permissions::from_mode(0o644)).unwrap();
+    let check = check_permissions(Path::new(PATH));
+    assert!(matches!(check.status, Status::Warn));
+    assert_eq!(
+        check.hint.as_deref(),
+        Some("chmod 600 ./test.doctor.credentials")
+    );
+
+    fs::set_permissions(PATH, fs::Permissions::from_mode(0o600)).unwrap();
+    assert!(matches!(
+        check_permissions(Path::new(PATH)).status,
+        Status::Pass
+    ));
+
+    fs::remove_file(PATH).unwrap();
+    assert!(matches!(
+        check_permissions(Path::new(PATH)).status,
+        Status::Fail
+    ));
+}
+
+#[test]
+fn init_file_detection() {
+    const PATH: &str = "./test.doctor.rc";
+    let missing = check_init_file(&Shell::Fish, Path::new(PATH));
+    assert!(matches!(missing.status, Status::Warn));
+    assert_eq!(
+        missing.hint.as_deref(),
+        Some("add 'awscredx init fish | source' to ./test.doctor.rc")
+    );
+
+    fs::write(PATH, "set -x EDITOR vim\n").unwrap();
+    assert!(matches!(
+        check_init_file(&Shell::Fish, Path::new(PATH)).status,
+        Status::Warn
+    ));
+    fs::write(PATH, "set -x EDITOR vim\nawscredx init fish | source\n").unwrap();
+    assert!(matches!(
+        check_init_file(&Shell::Fish, Path::new(PATH)).status,
+        Status::Pass
+    ));
+
+    fs::remove_file(PATH).unwrap();
+}
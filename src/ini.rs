@@ -0,0 +1,305 @@
+use std::fmt::{self, Display, Formatter};
+
+/// A format-preserving INI document.
+///
+/// Every line is kept verbatim together with its line terminator, so that a document
+/// that is parsed and written back without modifications is identical byte-for-byte.
+/// Modifications only rewrite the lines of the touched sections.
+#[derive(Debug, Default)]
+pub struct Ini {
+    preamble: Vec<Line>,
+    sections: Vec<Section>,
+}
+
+#[derive(Debug)]
+pub struct Section {
+    header: Line,
+    name: String,
+    lines: Vec<Line>,
+}
+
+#[derive(Debug)]
+struct Line(String);
+
+impl Line {
+    fn new(content: &str) -> Self {
+        Line(format!("{}\n", content))
+    }
+
+    fn content(&self) -> &str {
+        self.0.trim_end_matches(['\n', '\r'])
+    }
+
+    fn is_terminated(&self) -> bool {
+        self.0.ends_with('\n')
+    }
+
+    fn is_blank(&self) -> bool {
+        self.content().trim().is_empty()
+    }
+
+    fn is_comment(&self) -> bool {
+        let trimmed = self.content().trim_start();
+        trimmed.starts_with('#') || trimmed.starts_with(';')
+    }
+
+    fn section_name(&self) -> Option<&str> {
+        let line = self.content().trim();
+        if line.starts_with('[') {
+            Some(line.trim_matches(|c| "[ ]\"".contains(c)))
+        } else {
+            None
+        }
+    }
+
+    fn property(&self) -> Option<(&str, &str)> {
+        if self.is_comment() {
+            return None;
+        }
+        let mut parts = self.content().splitn(2, '=');
+        let key = parts.next()?.trim();
+        let value = parts.next()?;
+        if key.is_empty() {
+            None
+        } else {
+            Some((key, value.trim_matches([' ', '"'])))
+        }
+    }
+}
+
+impl Ini {
+    pub fn parse(content: &str) -> Self {
+        let mut ini = Self::default();
+        for raw in content.split_inclusive('\n') {
+            let line = Line(raw.to_owned());
+            if let Some(name) = line.section_name() {
+                let name = name.to_owned();
+                ini.sections.push(Section {
+                    header: line,
+                    name,
+                    lines: Vec::new(),
+                });
+            } else {
+                match ini.sections.last_mut() {
+                    Some(section) => section.lines.push(line),
+                    None => ini.preamble.push(line),
+                }
+            }
+        }
+        ini
+    }
+
+    pub fn sections(&self) -> impl Iterator<Item = &Section> {
+        self.sections.iter()
+    }
+
+    pub fn section(&self, name: &str) -> Option<&Section> {
+        self.sections.iter().find(|s| s.name == name)
+    }
+
+    /// Returns the section with the given name, appending an empty one at the end
+    /// of the document if it does not exist yet.
+    pub fn section_or_insert(&mut self, name: &str) -> &mut Section {
+        match self.sections.iter().position(|s| s.name == name) {
+            Some(ix) => &mut self.sections[ix],
+            None => {
+                let last = match self.sections.last_mut() {
+                    Some(section) => section.lines.last_mut().or(Some(&mut section.header)),
+                    None => self.preamble.last_mut(),
+                };
+                if let Some(last) = last {
+                    if !last.is_terminated() {
+                        last.0.push('\n');
+                    }
+                    if !last.is_blank() {
+                        self.sections
+                            .last_mut()
+                            .map(|s| &mut s.lines)
+                            .unwrap_or(&mut self.preamble)
+                            .push(Line::new(""));
+                    }
+                }
+                let header = if name.contains(' ') && !name.starts_with("profile ") {
+                    format!("[\"{}\"]", name)
+                } else {
+                    format!("[{}]", name)
+                };
+                self.sections.push(Section {
+                    header: Line::new(&header),
+                    name: name.to_owned(),
+                    lines: Vec::new(),
+                });
+                self.sections.last_mut().unwrap()
+            }
+        }
+    }
+
+    /// Removes the section with the given name.
+    /// Comments directly preceding the next section are kept as they most likely
+    /// describe the next section.
+    pub fn remove_section(&mut self, name: &str) -> bool {
+        let ix = match self.sections.iter().position(|s| s.name == name) {
+            Some(ix) => ix,
+            None => return false,
+        };
+        let mut section = self.sections.remove(ix);
+        let body_len = section.body_len();
+        let trailer: Vec<Line> = section.lines.drain(body_len..).collect();
+        if trailer.iter().any(Line::is_comment) {
+            match ix.checked_sub(1) {
+                Some(prev) => self.sections[prev].lines.extend(trailer),
+                None => self.preamble.extend(trailer),
+            }
+        }
+        true
+    }
+}
+
+impl Display for Ini {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        for line in &self.preamble {
+            f.write_str(&line.0)?;
+        }
+        for section in &self.sections {
+            f.write_str(&section.header.0)?;
+            for line in &section.lines {
+                f.write_str(&line.0)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Section {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.properties().find(|(k, _)| *k == key).map(|(_, v)| v)
+    }
+
+    pub fn properties(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.lines.iter().filter_map(Line::property)
+    }
+
+    /// Sets the property value. An existing property line is rewritten in place,
+    /// a new one is added after the last property of the section.
+    pub fn set(&mut self, key: &str, value: &str) {
+        let line = Line::new(&format!("{} = {}", key, value));
+        match self.position(key) {
+            Some(ix) => {
+                if self.lines[ix].property().map(|(_, v)| v) != Some(value) {
+                    self.lines[ix] = line;
+                }
+            }
+            None => {
+                let ix = self.body_len();
+                self.terminate_line_before(ix);
+                self.lines.insert(ix, line);
+            }
+        }
+    }
+
+    /// Checks whether the section contains a line with exactly this content.
+    pub fn contains_line(&self, content: &str) -> bool {
+        self.lines.iter().any(|l| l.content().trim() == content)
+    }
+
+    /// Inserts a line, e.g. a comment, directly after the section header.
+    pub fn insert_first_line(&mut self, content: &str) {
+        self.terminate_line_before(0);
+        self.lines.insert(0, Line::new(content));
+    }
+
+    pub fn remove(&mut self, key: &str) {
+        if let Some(ix) = self.position(key) {
+            self.lines.remove(ix);
+        }
+    }
+
+    fn position(&self, key: &str) -> Option<usize> {
+        self.lines
+            .iter()
+            .position(|l| l.property().map(|(k, _)| k) == Some(key))
+    }
+
+    /// Number of lines up to and including the last non-blank, non-comment line.
+    /// The remaining lines are the blank lines and comments separating this section
+    /// from the next one. In a section without properties the comments directly
+    /// after the header belong to the body.
+    fn body_len(&self) -> usize {
+        match self
+            .lines
+            .iter()
+            .rposition(|l| !l.is_blank() && !l.is_comment())
+        {
+            Some(ix) => ix + 1,
+            None => self.lines.iter().take_while(|l| l.is_comment()).count(),
+        }
+    }
+
+    fn terminate_line_before(&mut self, ix: usize) {
+        let prev = match ix.checked_sub(1) {
+            Some(p) => &mut self.lines[p],
+            None => &mut self.header,
+        };
+        if !prev.is_terminated() {
+            prev.0.push('\n');
+        }
+    }
+}
+
+#[test]
+fn round_trip_is_identical() {
+    let content = "; global comment\n\
+                   [default]\n\
+                   aws_access_key_id = AKIA\n\
+                   aws_secret_access_key=secret\n\
+                   region = eu-west-1 # trailing\n\
+                   \n\
+                   # comment about the next profile\n\
+                   [\"with space\"]\r\n\
+                   credential_process = /usr/bin/helper --profile x\r\n\
+                   \n\
+                   [no-newline]\n\
+                   output = json";
+    assert_eq!(Ini::parse(content).to_string(), content);
+    assert_eq!(Ini::parse("").to_string(), "");
+}
+
+#[test]
+fn modify_sections() {
+    let content = "# head\n[a]\nk1 = v1\n# about k2\nk2 = v2\n\n# about b\n[b]\nx = 1";
+    let mut ini = Ini::parse(content);
+    assert!(ini.section("with space").is_none());
+    let a = ini.section_or_insert("a");
+    assert_eq!(a.get("k2"), Some("v2"));
+    a.set("k1", "v1");
+    assert_eq!(ini.to_string(), content);
+
+    let a = ini.section_or_insert("a");
+    a.set("k2", "new");
+    a.set("k3", "v3");
+    a.remove("k1");
+    ini.section_or_insert("b").set("y", "2");
+    ini.section_or_insert("c d").set("z", "3");
+    assert_eq!(
+        ini.to_string(),
+        "# head\n[a]\n# about k2\nk2 = new\nk3 = v3\n\n# about b\n[b]\nx = 1\ny = 2\n\n[\"c d\"]\nz = 3\n"
+    );
+    ini.section_or_insert("c d").insert_first_line("# managed");
+    assert!(ini.section("c d").unwrap().contains_line("# managed"));
+
+    assert!(ini.remove_section("a"));
+    assert!(!ini.remove_section("a"));
+    assert_eq!(
+        ini.to_string(),
+        "# head\n\n# about b\n[b]\nx = 1\ny = 2\n\n[\"c d\"]\n# managed\nz = 3\n"
+    );
+
+    let e = ini.section_or_insert("e");
+    e.insert_first_line("# managed");
+    e.set("k", "v");
+    assert!(ini.to_string().ends_with("[e]\n# managed\nk = v\n"));
+}
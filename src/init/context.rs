@@ -1,11 +1,15 @@
 use std::path::PathBuf;
 
-use crate::{config, util};
+use crate::{styles, util};
 
 pub enum Shell {
     Fish,
     Bash,
     Zsh,
+    Nu,
+    Pwsh,
+    Elvish,
+    Xonsh,
 }
 
 impl From<&str> for Shell {
@@ -14,6 +18,10 @@ impl From<&str> for Shell {
             "fish" => Self::Fish,
             "bash" => Self::Bash,
             "zsh" => Self::Zsh,
+            "nu" => Self::Nu,
+            "pwsh" => Self::Pwsh,
+            "elvish" => Self::Elvish,
+            "xonsh" => Self::Xonsh,
             x => panic!("Unsupported shell {}", x),
         }
     }
@@ -25,14 +33,88 @@ impl AsRef<str> for Shell {
             Shell::Bash => "bash",
             Shell::Fish => "fish",
             Shell::Zsh => "zsh",
+            Shell::Nu => "nu",
+            Shell::Pwsh => "pwsh",
+            Shell::Elvish => "elvish",
+            Shell::Xonsh => "xonsh",
         }
     }
 }
 
-pub fn config_file() -> PathBuf {
-    util::path_to_absolute(config::CONFIG_FILE_PATH)
+impl Shell {
+    pub const ALL: [Shell; 7] = [
+        Shell::Bash,
+        Shell::Zsh,
+        Shell::Fish,
+        Shell::Nu,
+        Shell::Pwsh,
+        Shell::Elvish,
+        Shell::Xonsh,
+    ];
+
+    /// The line that initializes awscredx in the shell's init script.
+    pub fn init_line(&self) -> &'static str {
+        match self {
+            Shell::Bash => "eval $(awscredx init bash)",
+            Shell::Zsh => "eval $(awscredx init zsh)",
+            Shell::Fish => "awscredx init fish | source",
+            Shell::Nu => "awscredx init nu | save -f ~/.cache/awscredx.nu",
+            Shell::Pwsh => "awscredx init pwsh | Out-String | Invoke-Expression",
+            Shell::Elvish => "eval (awscredx init elvish | slurp)",
+            Shell::Xonsh => "execx($(awscredx init xonsh))",
+        }
+    }
+
+    /// The init script the init line goes into.
+    pub fn init_file(&self) -> Result<PathBuf, String> {
+        Ok(match self {
+            Shell::Bash => util::path_to_absolute("~/.bashrc")?,
+            Shell::Zsh => match util::env_path("ZDOTDIR")? {
+                Some(dir) => dir.join(".zshrc"),
+                None => util::path_to_absolute("~/.zshrc")?,
+            },
+            Shell::Fish => util::xdg_config_dir()?.join("fish/config.fish"),
+            // env.nu generates the script that config.nu sources
+            Shell::Nu => util::xdg_config_dir()?.join("nushell/env.nu"),
+            Shell::Pwsh => {
+                util::xdg_config_dir()?.join("powershell/Microsoft.PowerShell_profile.ps1")
+            }
+            Shell::Elvish => util::xdg_config_dir()?.join("elvish/rc.elv"),
+            Shell::Xonsh => util::path_to_absolute("~/.xonshrc")?,
+        })
+    }
+}
+
+/// Lists the init line of every shell, one per line with the given indentation.
+pub fn init_instructions(indent: &str) -> String {
+    Shell::ALL
+        .iter()
+        .map(|shell| {
+            let line = styles::path().paint(shell.init_line());
+            let location = match shell {
+                Shell::Nu => format!(
+                    " in env.nu and {} in config.nu",
+                    styles::path().paint("source ~/.cache/awscredx.nu")
+                ),
+                Shell::Pwsh => " in $PROFILE".to_owned(),
+                _ => String::new(),
+            };
+            format!(
+                "{}- {}{} if you use {}",
+                indent,
+                line,
+                location,
+                shell.as_ref()
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(",\n")
+}
+
+pub fn config_file() -> Result<PathBuf, String> {
+    util::config_file()
 }
 
-pub fn config_dir() -> PathBuf {
-    config_file().parent().unwrap().to_path_buf()
+pub fn config_dir() -> Result<PathBuf, String> {
+    Ok(config_file()?.parent().unwrap().to_path_buf())
 }
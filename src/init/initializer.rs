@@ -1,7 +1,12 @@
 use std::{env, fs};
 
+use super::context::Shell;
 use crate::init::SHELL_VAR;
-use crate::util;
+use crate::{util, web_console};
+
+/// Subcommands offered by the shell completion.
+const COMPLETION_COMMANDS: &str = "assume unassume exec credential-process whoami logout \
+list-profiles list-credentials web-console-signin doctor init setup version";
 
 pub enum InitType {
     Bootstrap,
@@ -9,42 +14,76 @@ pub enum InitType {
 }
 
 pub fn run(shell: &str, init_type: InitType) {
+    let shell = Shell::from(shell);
     let buf = env::current_exe().unwrap();
     let current_binary_path = buf.to_str().unwrap();
 
     use InitType::*;
     match init_type {
-        Bootstrap => {
+        // Nushell sources files at parse time only, so it always gets the full script.
+        Bootstrap if !matches!(shell, Shell::Nu) => {
             let cmd = format!(
                 r#""{bin}" init --full {shell}"#,
                 bin = current_binary_path,
-                shell = shell
+                shell = shell.as_ref()
             );
-            if shell == "fish" {
-                print!(r#"source ({cmd} | psub)"#, cmd = cmd);
-            } else {
-                print!(r#"source <({cmd})"#, cmd = cmd);
+            match shell {
+                Shell::Fish => print!(r#"source ({cmd} | psub)"#, cmd = cmd),
+                Shell::Pwsh => print!(r#"& {cmd} | Out-String | Invoke-Expression"#, cmd = cmd),
+                Shell::Elvish => print!(r#"eval ({cmd} | slurp)"#, cmd = cmd),
+                Shell::Xonsh => print!(r#"execx($({cmd}))"#, cmd = cmd),
+                _ => print!(r#"source <({cmd})"#, cmd = cmd),
             }
         }
-        Full => {
+        _ => {
             delete_deprecated_script("script.sh");
             delete_deprecated_script("script.fish");
-            let tmpl = if shell == "fish" {
-                include_str!("templates/init.fish")
-            } else {
-                include_str!("templates/init.sh")
-            }
-            .replace("@bin@", current_binary_path)
-            .replace("@shell_var@", SHELL_VAR)
-            .replace("@shell@", shell);
+            let (init, completion) = match shell {
+                Shell::Fish => (
+                    include_str!("templates/init.fish"),
+                    include_str!("templates/completion.fish"),
+                ),
+                Shell::Bash => (
+                    include_str!("templates/init.sh"),
+                    include_str!("templates/completion.bash"),
+                ),
+                Shell::Zsh => (
+                    include_str!("templates/init.sh"),
+                    include_str!("templates/completion.zsh"),
+                ),
+                Shell::Nu => (
+                    include_str!("templates/init.nu"),
+                    include_str!("templates/completion.nu"),
+                ),
+                Shell::Pwsh => (
+                    include_str!("templates/init.ps1"),
+                    include_str!("templates/completion.ps1"),
+                ),
+                Shell::Elvish => (
+                    include_str!("templates/init.elv"),
+                    include_str!("templates/completion.elv"),
+                ),
+                Shell::Xonsh => (
+                    include_str!("templates/init.xsh"),
+                    include_str!("templates/completion.xsh"),
+                ),
+            };
+            let tmpl = format!("{}{}", init, completion)
+                .replace("@commands@", COMPLETION_COMMANDS)
+                .replace("@services@", &web_console::SERVICES.join(" "))
+                .replace("@bin@", current_binary_path)
+                .replace("@shell_var@", SHELL_VAR)
+                .replace("@shell@", shell.as_ref());
             println!("{}", tmpl);
         }
     }
 }
 
 fn delete_deprecated_script(file: &str) {
-    let dir = util::path_to_absolute(util::STORAGE_DIR);
-    let file = dir.join(file);
+    let file = match util::storage_dir() {
+        Ok(dir) => dir.join(file),
+        Err(_) => return,
+    };
     if file.exists() {
         fs::remove_file(&file).expect("cannot delete file");
     }
@@ -2,6 +2,9 @@ mod context;
 mod initializer;
 pub mod setup;
 
+pub use context::{init_instructions, Shell};
 pub use initializer::*;
 
 pub const SHELL_VAR: &str = "AWSCREDX_SHELL";
+/// Set by `assume` also when `AWS_PROFILE` is not exported.
+pub const PROFILE_VAR: &str = "AWSCREDX_PROFILE";
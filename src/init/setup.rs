@@ -22,17 +22,13 @@ fn run_jobs() -> Result<(), String> {
 Make sure that you:
   {} have filled all necessarily properties in your configuration file {},
   {} have the following line in your shell init script, e.g. .bashrc
-     - {} if you use bash,
-     - {} if you use zsh,
-     - {} if you use fish
+{}
 
 Now you can open a new terminal and assume a role by calling '{}'."#,
         styles::number().paint("1."),
-        styles::path().paint(context::config_file().to_str().unwrap()),
+        styles::path().paint(context::config_file()?.to_str().unwrap()),
         styles::number().paint("2."),
-        styles::path().paint("eval $(awscredx init bash)"),
-        styles::path().paint("eval $(awscredx init zsh)"),
-        styles::path().paint("awscredx init fish | source"),
+        context::init_instructions("     "),
         styles::number().paint("assume <profile-from-your-config>")
     );
 
@@ -40,36 +36,28 @@ Now you can open a new terminal and assume a role by calling '{}'."#,
 }
 
 fn create_config_dir() -> Result<(), String> {
-    if context::config_dir().exists() {
+    let dir = context::config_dir()?;
+    if dir.exists() {
         Ok(())
     } else {
-        fs::create_dir_all(&context::config_dir()).map_err(|e| {
-            format!(
-                "cannot create directory {}: {}",
-                context::config_dir().display(),
-                e
-            )
-        })?;
-        util::set_permissions(&context::config_dir(), 0o700);
+        fs::create_dir_all(&dir)
+            .map_err(|e| format!("cannot create directory {}: {}", dir.display(), e))?;
+        util::set_permissions(&dir, 0o700);
         Ok(())
     }
 }
 
 fn create_config_file() -> Result<(), String> {
-    if context::config_file().exists() {
+    let path = context::config_file()?;
+    if path.exists() {
         Ok(())
     } else {
-        let file = File::create(&context::config_file()).map_err(|e| {
-            format!(
-                "cannot create configuration file {}: {}",
-                context::config_file().display(),
-                e
-            )
-        })?;
+        let file = File::create(&path)
+            .map_err(|e| format!("cannot create configuration file {}: {}", path.display(), e))?;
         let content = include_str!("templates/config.toml");
         write!(&file, "{}", content)
             .map_err(|e| format!("cannot write configuration file: {}", e))?;
-        util::set_permissions(&context::config_file(), 0o600);
+        util::set_permissions(&path, 0o600);
         Ok(())
     }
 }
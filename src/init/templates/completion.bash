@@ -0,0 +1,45 @@
+
+__awscredx_profiles() {
+  "@bin@" list-profiles --names 2>/dev/null
+}
+
+__awscredx_assume_completion() {
+  local cur="${COMP_WORDS[COMP_CWORD]}"
+  if [[ "$cur" == -* ]]; then
+    COMPREPLY=($(compgen -W "--force --export-keys" -- "$cur"))
+  else
+    COMPREPLY=($(compgen -W "$(__awscredx_profiles)" -- "$cur"))
+  fi
+}
+complete -F __awscredx_assume_completion assume
+
+__awscredx_completion() {
+  local cur="${COMP_WORDS[COMP_CWORD]}"
+  local prev="${COMP_WORDS[COMP_CWORD-1]}"
+  case "$prev" in
+    --service)
+      COMPREPLY=($(compgen -W "@services@" -- "$cur"))
+      return
+      ;;
+    --config)
+      COMPREPLY=($(compgen -f -- "$cur"))
+      return
+      ;;
+  esac
+  if [ "$COMP_CWORD" -eq 1 ]; then
+    COMPREPLY=($(compgen -W "@commands@" -- "$cur"))
+    return
+  fi
+  case "${COMP_WORDS[1]}" in
+    assume|exec|credential-process|whoami|logout)
+      COMPREPLY=($(compgen -W "$(__awscredx_profiles)" -- "$cur"))
+      ;;
+    init)
+      COMPREPLY=($(compgen -W "bash zsh fish nu pwsh elvish xonsh" -- "$cur"))
+      ;;
+    web-console-signin)
+      COMPREPLY=($(compgen -W "--service --open-in-browser" -- "$cur"))
+      ;;
+  esac
+}
+complete -F __awscredx_completion awscredx
@@ -0,0 +1,16 @@
+
+function __awscredx_profiles
+  "@bin@" list-profiles --names 2>/dev/null
+end
+
+complete -c assume -f -a '(__awscredx_profiles)'
+complete -c assume -s f -l force -d 'Ignore cached credentials'
+complete -c assume -l export-keys -d 'Export the access keys'
+
+set -l __awscredx_commands @commands@
+complete -c awscredx -f -n "not __fish_seen_subcommand_from $__awscredx_commands" -a "$__awscredx_commands"
+complete -c awscredx -f -n '__fish_seen_subcommand_from assume exec credential-process whoami logout' -a '(__awscredx_profiles)'
+complete -c awscredx -f -n '__fish_seen_subcommand_from init' -a 'bash zsh fish nu pwsh elvish xonsh'
+complete -c awscredx -f -n '__fish_seen_subcommand_from web-console-signin' -l service -r -a '@services@'
+complete -c awscredx -f -n '__fish_seen_subcommand_from web-console-signin' -l open-in-browser
+complete -c awscredx -l config -r -F
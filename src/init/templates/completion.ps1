@@ -0,0 +1,30 @@
+
+function __awscredx_profiles {
+  & "@bin@" list-profiles --names 2>$null
+}
+
+Register-ArgumentCompleter -Native -CommandName assume -ScriptBlock {
+  param($wordToComplete, $commandAst, $cursorPosition)
+  $candidates = if ($wordToComplete -like '-*') { '--force', '--export-keys' } else { __awscredx_profiles }
+  $candidates | Where-Object { $_ -like "$wordToComplete*" } |
+    ForEach-Object { [System.Management.Automation.CompletionResult]::new($_) }
+}
+
+Register-ArgumentCompleter -Native -CommandName awscredx -ScriptBlock {
+  param($wordToComplete, $commandAst, $cursorPosition)
+  # the words before the one being completed
+  $words = @($commandAst.CommandElements | Where-Object { $_.Extent.EndOffset -lt $cursorPosition } | ForEach-Object { "$_" })
+  $candidates = if ($words[-1] -eq '--service') {
+    '@services@' -split ' '
+  } elseif ($words.Count -eq 1) {
+    '@commands@' -split ' '
+  } else {
+    switch ($words[1]) {
+      { $_ -in 'assume', 'exec', 'credential-process', 'whoami', 'logout' } { __awscredx_profiles }
+      'init' { 'bash', 'zsh', 'fish', 'nu', 'pwsh', 'elvish', 'xonsh' }
+      'web-console-signin' { '--service', '--open-in-browser' }
+    }
+  }
+  $candidates | Where-Object { $_ -like "$wordToComplete*" } |
+    ForEach-Object { [System.Management.Automation.CompletionResult]::new($_) }
+}
@@ -18,3 +18,9 @@ function assume
       return $s
   end
 end
+
+function unassume
+  "@bin@" unassume | source
+  functions -e fish_prompt
+  functions -c _original_fish_prompt fish_prompt
+end
@@ -0,0 +1,21 @@
+$env:@shell_var@ = "@shell@"
+if (-not $global:_AWSCREDX_ORIGINAL_PROMPT) {
+  $global:_AWSCREDX_ORIGINAL_PROMPT = $function:prompt
+}
+
+function __awscredx_prompt {
+  & "@bin@" print-prompt
+}
+
+function assume {
+  $out = & "@bin@" assume @args
+  if ($LASTEXITCODE -ne 0) {
+    return
+  }
+  Invoke-Expression ($out -join "`n")
+}
+
+function unassume {
+  Invoke-Expression ((& "@bin@" unassume) -join "`n")
+  $function:global:prompt = $global:_AWSCREDX_ORIGINAL_PROMPT
+}
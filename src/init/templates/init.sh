@@ -12,10 +12,15 @@ function assume {
   s=$?
   case $s in
     0)
-      eval $out
+      eval "$out"
       ;;
     *)
       return $s
       ;;
   esac
 }
+
+function unassume {
+  eval "$("@bin@" unassume)"
+  PS1="${_ORIGINAL_PS1:-}"
+}
@@ -0,0 +1,151 @@
+use std::{fs, process};
+
+use crate::assume;
+use crate::config::Config;
+use crate::credentials::{CredentialsFile, ProfileName};
+use crate::{styles, util};
+
+/// Removes the temporary credentials of the profile, or of all profiles with `all`,
+/// from the credentials file and the cache of `credential-process`.
+/// The main profile is never touched.
+pub fn run(profile: Option<&str>, all: bool) {
+    if let Err(e) = logout(profile, all) {
+        eprintln!("{}: {}", &util::styled_error_word(), e);
+        process::exit(1);
+    }
+}
+
+fn logout(profile: Option<&str>, all: bool) -> Result<(), String> {
+    let config = Config::read()?.ok_or("Config file does not exist")?;
+    let profiles = if all {
+        managed_profiles(&config)
+    } else {
+        let name = profile
+            .map(str::to_owned)
+            .or_else(util::current_profile)
+            .ok_or("no profile given and AWS_PROFILE is not set")?;
+        vec![ProfileName::new(name)]
+    };
+    let mut removed = Vec::new();
+    remove_credentials(
+        &config,
+        &mut CredentialsFile::read_default()?,
+        &profiles,
+        &mut removed,
+    )?;
+    remove_credentials(
+        &config,
+        &mut assume::credential_process_cache()?,
+        &profiles,
+        &mut removed,
+    )?;
+
+    if removed.is_empty() {
+        println!("No temporary credentials to remove");
+    } else {
+        println!("Removed credentials of {}", removed.join(", "));
+    }
+    if all {
+        let path = assume::sso_token_cache_path()?;
+        if path.exists() {
+            fs::remove_file(&path)
+                .map_err(|e| format!("cannot remove {}: {}", path.display(), e))?;
+            println!(
+                "Removed SSO access token {}",
+                styles::path().paint(path.to_string_lossy())
+            );
+        }
+    }
+    Ok(())
+}
+
+/// The profiles awscredx writes credentials for. Other sections, even with
+/// temporary credentials, belong to the user or other tools.
+fn managed_profiles(config: &Config) -> Vec<ProfileName> {
+    config
+        .profiles
+        .keys()
+        .chain(Some(&config.mfa_profile))
+        .cloned()
+        .collect()
+}
+
+/// Adds the profiles whose credentials have been removed to `removed`.
+fn remove_credentials<'a>(
+    config: &Config,
+    cred_file: &mut CredentialsFile,
+    profiles: &'a [ProfileName],
+    removed: &mut Vec<&'a str>,
+) -> Result<(), String> {
+    let _lock = cred_file.lock()?;
+    cred_file.reload()?;
+    for p in profiles.iter().filter(|p| *p != &config.main_profile) {
+        if cred_file.remove_temporary_credentials(p) && !removed.contains(&p.as_ref()) {
+            removed.push(p.as_ref());
+        }
+    }
+    cred_file.write()
+}
+
+#[test]
+fn logout_all_keeps_foreign_sessions() {
+    use std::path::Path;
+
+    const CONFIG_PATH: &str = "./test.logout.config";
+    const PATH: &str = "./test.logout";
+    const EXPIRATIONS_PATH: &str = "./test.logout.expirations.toml";
+    const CACHE_PATH: &str = "./test.logout.cache";
+    const CACHE_EXPIRATIONS_PATH: &str = "./test.logout.cache-expirations.toml";
+
+    fs::write(
+        CONFIG_PATH,
+        "main_profile = 'main'\nmfa_serial_number = 'mfa'\n[profiles]\ndev = 'arn:aws:iam::1:role/Dev'\n",
+    )
+    .unwrap();
+    let config = Config::read_raw(Path::new(CONFIG_PATH));
+    fs::remove_file(CONFIG_PATH).unwrap();
+    let config = config.unwrap().unwrap();
+
+    let session = "aws_access_key_id = k\naws_secret_access_key = s\naws_session_token = t\n";
+    fs::write(
+        PATH,
+        format!(
+            "[main]\naws_access_key_id = k\naws_secret_access_key = s\n\n\
+             [dev]\n{s}\n[main-mfa]\n{s}\n[sso-tool]\n{s}",
+            s = session
+        ),
+    )
+    .unwrap();
+    fs::write(
+        CACHE_PATH,
+        format!("[main-mfa]\n{s}\n[dev]\n{s}", s = session),
+    )
+    .unwrap();
+
+    let profiles = managed_profiles(&config);
+    let mut removed = Vec::new();
+    let mut cred_file = CredentialsFile::read(PATH, EXPIRATIONS_PATH).unwrap();
+    remove_credentials(&config, &mut cred_file, &profiles, &mut removed).unwrap();
+    let mut cache = CredentialsFile::read(CACHE_PATH, CACHE_EXPIRATIONS_PATH).unwrap();
+    remove_credentials(&config, &mut cache, &profiles, &mut removed).unwrap();
+    assert_eq!(removed, vec!["dev", "main-mfa"]);
+    assert_eq!(fs::read_to_string(CACHE_PATH).unwrap(), "");
+    assert_eq!(
+        fs::read_to_string(PATH).unwrap(),
+        format!(
+            "[main]\naws_access_key_id = k\naws_secret_access_key = s\n\n[sso-tool]\n{}",
+            session
+        )
+    );
+
+    for path in &[
+        PATH,
+        EXPIRATIONS_PATH,
+        "./test.logout.expirations.lock",
+        CACHE_PATH,
+        CACHE_EXPIRATIONS_PATH,
+        "./test.logout.cache-expirations.lock",
+    ] {
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -1,16 +1,20 @@
-use std::env;
-
 use ansi_term::{Color, Style};
-use chrono::{DateTime, Duration, Local, Utc};
+use chrono::{DateTime, Local};
 
 use crate::config::Config;
 use crate::credentials::CredentialsFile;
 use crate::init::{setup, InitType};
 
 mod assume;
+mod aws_config;
 mod config;
 mod credentials;
+mod doctor;
+mod ini;
 mod init;
+mod logout;
+mod picker;
+mod prompt;
 mod state;
 mod styles;
 mod util;
@@ -27,23 +31,92 @@ fn main() {
     const COMMAND_PRINT_EXPIRATION: &str = "print-expiration";
     const COMMAND_VERSION: &str = "version";
     const COMMAND_WEB_CONSOLE_SIGNIN: &str = "web-console-signin";
+    const COMMAND_CREDENTIAL_PROCESS: &str = "credential-process";
+    const COMMAND_EXEC: &str = "exec";
+    const COMMAND_UNASSUME: &str = "unassume";
+    const COMMAND_LOGOUT: &str = "logout";
+    const COMMAND_WHOAMI: &str = "whoami";
+    const COMMAND_DOCTOR: &str = "doctor";
 
     const ARG_PROFILE_NAME: &str = "profile-name";
     const ARG_WEB_CONSOLE_SERVICE: &str = "service";
     const ARG_OPEN_IN_BROWSER: &str = "open-in-browser";
     const ARG_SHELL: &str = "shell";
     const ARG_FULL: &str = "full";
+    const ARG_CONFIG: &str = "config";
+    const ARG_FORCE: &str = "force";
+    const ARG_PRINT_CONFIG: &str = "print-config";
+    const ARG_EXPORT_KEYS: &str = "export-keys";
+    const ARG_ALL: &str = "all";
+    const ARG_JSON: &str = "json";
+    const ARG_NAMES: &str = "names";
+    const ARG_NO_STORE: &str = "no-store";
+    const ARG_COMMAND: &str = "command";
+    const ARG_NO_COLOR: &str = "no-color";
 
     let matches = clap::App::new("awscredx")
         .version(version::VERSION)
         .about(format!(r#"AWS credentials management, a.k.a. role assumption made easy.
 Run '{}' to create the configuration file and set up shell scripts."#,
                        Style::new().fg(Color::Yellow).paint("awscredx setup")).as_str())
+        .arg(clap::Arg::with_name(ARG_CONFIG)
+            .long(ARG_CONFIG)
+            .value_name("FILE")
+            .global(true)
+            .takes_value(true)
+            .help("Configuration file to use instead of $XDG_CONFIG_HOME/awscredx/config.toml"))
         .subcommand(clap::SubCommand::with_name(COMMAND_ASSUME)
             .about("Prints shell commands to assume the role for a given profile")
             .arg(clap::Arg::with_name(ARG_PROFILE_NAME)
+                .help("Profile name which role to assume, opens a profile picker if omitted"))
+            .arg(clap::Arg::with_name(ARG_FORCE)
+                .long(ARG_FORCE)
+                .short("f")
+                .help("Ignores cached credentials and requests new ones, including the MFA session"))
+            .arg(clap::Arg::with_name(ARG_EXPORT_KEYS)
+                .long(ARG_EXPORT_KEYS)
+                .help("Exports the access keys and session token instead of AWS_PROFILE")))
+        .subcommand(clap::SubCommand::with_name(COMMAND_UNASSUME)
+            .about("Prints shell commands to clear the variables set by assume"))
+        .subcommand(clap::SubCommand::with_name(COMMAND_WHOAMI)
+            .about("Shows the identity behind the credentials of a profile ($AWS_PROFILE by default)")
+            .arg(clap::Arg::with_name(ARG_PROFILE_NAME)
+                .help("Profile name which identity to show"))
+            .arg(clap::Arg::with_name(ARG_JSON)
+                .long(ARG_JSON)
+                .help("Prints the identity as JSON")))
+        .subcommand(clap::SubCommand::with_name(COMMAND_DOCTOR)
+            .about("Checks the configuration, credentials and shell setup"))
+        .subcommand(clap::SubCommand::with_name(COMMAND_LOGOUT)
+            .about("Removes cached temporary credentials of a profile ($AWS_PROFILE by default)")
+            .arg(clap::Arg::with_name(ARG_PROFILE_NAME)
+                .help("Profile name which credentials to remove"))
+            .arg(clap::Arg::with_name(ARG_ALL)
+                .long(ARG_ALL)
+                .conflicts_with(ARG_PROFILE_NAME)
+                .help("Removes all temporary credentials and the SSO access token")))
+        .subcommand(clap::SubCommand::with_name(COMMAND_CREDENTIAL_PROCESS)
+            .about("Prints credentials of a profile as JSON for 'credential_process' in ~/.aws/config")
+            .arg(clap::Arg::with_name(ARG_PROFILE_NAME)
+                .required_unless(ARG_PRINT_CONFIG)
+                .help("Profile name which credentials to print"))
+            .arg(clap::Arg::with_name(ARG_PRINT_CONFIG)
+                .long(ARG_PRINT_CONFIG)
+                .help("Prints ~/.aws/config sections using awscredx as credential process for all profiles")))
+        .subcommand(clap::SubCommand::with_name(COMMAND_EXEC)
+            .about("Runs a command with the credentials of a profile in its environment")
+            .usage("awscredx exec [--no-store] <profile-name> -- <command>...")
+            .arg(clap::Arg::with_name(ARG_PROFILE_NAME)
+                .required(true)
+                .help("Profile name which role to assume"))
+            .arg(clap::Arg::with_name(ARG_NO_STORE)
+                .long(ARG_NO_STORE)
+                .help("Does not write any credentials into the credentials file"))
+            .arg(clap::Arg::with_name(ARG_COMMAND)
                 .required(true)
-                .help("Profile name which role to assume")))
+                .multiple(true)
+                .last(true)
+                .help("Command with its arguments")))
         .subcommand(clap::SubCommand::with_name(COMMAND_SETUP)
             .about("Creates config.toml")
         )
@@ -52,7 +125,7 @@ Run '{}' to create the configuration file and set up shell scripts."#,
             .arg(clap::Arg::with_name(ARG_SHELL)
                 .value_name("SHELL")
                 .required(true)
-                .possible_values(&["fish", "bash", "zsh"])
+                .possible_values(&["fish", "bash", "zsh", "nu", "pwsh", "elvish", "xonsh"])
                 .help("The name of the currently running shell")
             )
             .arg(clap::Arg::with_name(ARG_FULL)
@@ -61,7 +134,10 @@ Run '{}' to create the configuration file and set up shell scripts."#,
             )
         )
         .subcommand(clap::SubCommand::with_name(COMMAND_LIST_PROFILES)
-            .about("Lists configured profiles with their role ARNs"))
+            .about("Lists configured profiles with their role ARNs")
+            .arg(clap::Arg::with_name(ARG_NAMES)
+                .long(ARG_NAMES)
+                .help("Prints only the names of the configured profiles, e.g. for shell completion")))
         .subcommand(clap::SubCommand::with_name(COMMAND_LIST_CREDENTIALS)
             .about("Lists current credentials with their expiration times"))
         .subcommand(clap::SubCommand::with_name(COMMAND_WEB_CONSOLE_SIGNIN)
@@ -75,21 +151,80 @@ Run '{}' to create the configuration file and set up shell scripts."#,
                 .long(ARG_OPEN_IN_BROWSER)
                 .help("Does not print the web console sign-in URL but opens the URL in browser")))
         .subcommand(clap::SubCommand::with_name(COMMAND_PRINT_PROMPT)
-            .about("Prints prompt part for the current profile ($AWS_PROFILE)"))
+            .about("Prints prompt part for the current profile ($AWS_PROFILE)")
+            .arg(clap::Arg::with_name(ARG_NO_COLOR)
+                .long(ARG_NO_COLOR)
+                .help("Prints without colors, also if NO_COLOR is set")))
         .subcommand(clap::SubCommand::with_name(COMMAND_PRINT_EXPIRATION)
-            .about("Prints expiration for the current profile ($AWS_PROFILE)"))
+            .about("Prints expiration for the current profile ($AWS_PROFILE)")
+            .arg(clap::Arg::with_name(ARG_NO_COLOR)
+                .long(ARG_NO_COLOR)
+                .help("Prints without colors, also if NO_COLOR is set")))
         .subcommand(clap::SubCommand::with_name(COMMAND_VERSION)
             .about("Shows current version and checks for newer version"))
         .setting(clap::AppSettings::SubcommandRequiredElseHelp)
         .get_matches();
 
+    if let Some(path) = matches
+        .value_of(ARG_CONFIG)
+        .or_else(|| matches.subcommand().1.and_then(|m| m.value_of(ARG_CONFIG)))
+    {
+        if let Err(e) = util::set_config_file(path) {
+            eprintln!("{}: {}", styles::error().paint("ERROR"), e);
+            ::std::process::exit(1);
+        }
+    }
+
     match matches.subcommand() {
         (COMMAND_ASSUME, Some(arg)) => {
             let config = read_config();
-            assume::run(arg.value_of(ARG_PROFILE_NAME).unwrap(), &config)
+            let export = if arg.is_present(ARG_EXPORT_KEYS) {
+                config.export.with_keys()
+            } else {
+                config.export
+            };
+            let profile = match arg.value_of(ARG_PROFILE_NAME) {
+                Some(p) => p.to_owned(),
+                None => pick_profile(&config),
+            };
+            assume::run(&profile, arg.is_present(ARG_FORCE), export, &config)
+        }
+        (COMMAND_CREDENTIAL_PROCESS, Some(arg)) => {
+            let config = read_config();
+            match arg.value_of(ARG_PROFILE_NAME) {
+                Some(profile) if !arg.is_present(ARG_PRINT_CONFIG) => {
+                    assume::credential_process(profile, &config)
+                }
+                _ => aws_config::print_config(&config),
+            }
+        }
+        (COMMAND_EXEC, Some(arg)) => {
+            let config = read_config();
+            let command: Vec<&str> = arg.values_of(ARG_COMMAND).unwrap().collect();
+            assume::exec(
+                arg.value_of(ARG_PROFILE_NAME).unwrap(),
+                arg.is_present(ARG_NO_STORE),
+                &command,
+                &config,
+            )
+        }
+        (COMMAND_UNASSUME, _) => assume::unassume(),
+        (COMMAND_WHOAMI, Some(arg)) => {
+            let config = read_config();
+            assume::whoami(
+                arg.value_of(ARG_PROFILE_NAME),
+                arg.is_present(ARG_JSON),
+                &config,
+            )
+        }
+        (COMMAND_DOCTOR, _) => doctor::run(),
+        (COMMAND_LOGOUT, Some(arg)) => {
+            logout::run(arg.value_of(ARG_PROFILE_NAME), arg.is_present(ARG_ALL))
+        }
+        (COMMAND_PRINT_PROMPT, Some(m)) => prompt::print_prompt(!m.is_present(ARG_NO_COLOR)),
+        (COMMAND_PRINT_EXPIRATION, Some(m)) => {
+            prompt::print_expiration(!m.is_present(ARG_NO_COLOR))
         }
-        (COMMAND_PRINT_PROMPT, _) => print_prompt(),
-        (COMMAND_PRINT_EXPIRATION, _) => print_expiration(),
         (COMMAND_INIT, Some(args)) => init::run(
             args.value_of(ARG_SHELL).expect("shell"),
             if args.is_present(ARG_FULL) {
@@ -99,6 +234,7 @@ Run '{}' to create the configuration file and set up shell scripts."#,
             },
         ),
         (COMMAND_SETUP, _) => setup::run(),
+        (COMMAND_LIST_PROFILES, Some(arg)) if arg.is_present(ARG_NAMES) => print_profile_names(),
         (COMMAND_LIST_PROFILES, _) => print_profiles(),
         (COMMAND_LIST_CREDENTIALS, _) => print_credentials(),
         (COMMAND_WEB_CONSOLE_SIGNIN, Some(arg)) => web_console::create_signin_url(
@@ -115,7 +251,7 @@ fn read_config() -> Config {
         Ok(Some(config)) => config,
         Ok(None) => {
             println!("configuration file {} does not exist.\nRun 'awscredx init' to initialize your working environment.",
-                     config::CONFIG_FILE_PATH);
+                     util::config_file().unwrap_or_default().display());
             ::std::process::exit(1);
         }
         Err(e) => {
@@ -129,6 +265,27 @@ fn read_config() -> Config {
     }
 }
 
+/// Exits if the user cancels the picker.
+fn pick_profile(config: &Config) -> String {
+    match picker::pick(config) {
+        Ok(Some(profile)) => profile,
+        Ok(None) => std::process::exit(130),
+        Err(e) => {
+            eprintln!("{}: {}", util::styled_error_word(), e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Used by the shell completion, so it prints nothing if the config cannot be read.
+fn print_profile_names() {
+    if let Ok(Some(c)) = Config::read() {
+        for name in c.profiles.keys() {
+            println!("{}", name);
+        }
+    }
+}
+
 fn print_profiles() {
     let c = read_config();
     let max_profile_name = c
@@ -145,7 +302,7 @@ fn print_profiles() {
         width = width
     );
     for (name, prof) in c.profiles.iter() {
-        println!("{:width$}{}", name, &prof.role_arn, width = width);
+        println!("{:width$}{}", name, prof.target(), width = width);
     }
 }
 
@@ -171,7 +328,7 @@ fn print_credentials() {
                         println!(
                             "at {} in {}",
                             time_style.paint(local_time.format("%H:%M").to_string()),
-                            time_style.paint(format_duration(local_time - Local::now()))
+                            time_style.paint(util::format_duration(local_time - Local::now()))
                         );
                     }
                     None => println!("{}", time_style.paint("never")),
@@ -184,81 +341,3 @@ fn print_credentials() {
         }
     }
 }
-
-fn format_duration(d: Duration) -> String {
-    format!("{}:{:02}", d.num_hours(), d.num_minutes() % 60)
-}
-
-fn print_prompt() {
-    if let Ok(profile) = env::var("AWS_PROFILE") {
-        match credentials::CredentialExpirations::get(&profile) {
-            Ok(Some(ex)) => {
-                let duration = ex - Utc::now();
-                let expiration_style = if duration > Duration::minutes(10) {
-                    Style::new().fg(Color::Green)
-                } else {
-                    Style::new().fg(Color::Yellow).bold()
-                };
-                if duration > Duration::zero() {
-                    print!(
-                        "[{} {}]",
-                        Style::new()
-                            .fg(Color::White)
-                            .bold()
-                            .paint(profile)
-                            .to_string(),
-                        expiration_style
-                            .paint(format_duration(duration))
-                            .to_string(),
-                    )
-                }
-            }
-            Ok(None) => print!(
-                "[{} {}]",
-                Style::new()
-                    .fg(Color::White)
-                    .bold()
-                    .paint(profile)
-                    .to_string(),
-                Style::new()
-                    .fg(Color::Red)
-                    .bold()
-                    .paint("expired")
-                    .to_string(),
-            ),
-            Err(e) => eprintln!("ERROR: {}", e),
-        }
-    }
-}
-
-fn print_expiration() {
-    if let Ok(profile) = env::var("AWS_PROFILE") {
-        match credentials::CredentialExpirations::get(&profile) {
-            Ok(Some(ex)) => {
-                let duration = ex - Utc::now();
-                let expiration_style = if duration > Duration::minutes(10) {
-                    Style::new().fg(Color::Green)
-                } else {
-                    Style::new().fg(Color::Yellow).bold()
-                };
-                if duration > Duration::zero() {
-                    print!(
-                        "{}",
-                        expiration_style
-                            .paint(format_duration(duration))
-                            .to_string()
-                    )
-                }
-            }
-            Ok(None) => print!(
-                "{}",
-                Style::new()
-                    .fg(Color::Red)
-                    .bold()
-                    .paint("expired")
-                    .to_string()
-            ),
-            Err(e) => eprintln!("ERROR: {}", e),
-        }
-    }
-}
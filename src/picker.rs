@@ -0,0 +1,255 @@
+use std::io::{stderr, Stderr, Write};
+
+use chrono::Utc;
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::style::{Attribute, Color, Print, SetAttribute, SetForegroundColor};
+use crossterm::{cursor, execute, queue, terminal};
+
+use crate::config::Config;
+use crate::credentials::CredentialsFile;
+
+struct Item<'a> {
+    name: &'a str,
+    target: String,
+    description: &'a str,
+    /// Lowercase text that the query is matched against.
+    haystack: String,
+    cached: bool,
+}
+
+/// Lets the user pick a profile in an interactive fuzzy finder drawn on stderr.
+/// Returns `None` if the user cancelled.
+pub fn pick(config: &Config) -> Result<Option<String>, String> {
+    let cred_file = CredentialsFile::read_default()?;
+    let now = Utc::now();
+    let cached: Vec<&str> = cred_file
+        .get_current_credentials_data()
+        .filter(|c| c.expires_at.is_some_and(|exp| exp > now))
+        .map(|c| c.profile_name)
+        .collect();
+    let items: Vec<Item> = config
+        .profiles
+        .iter()
+        .map(|(name, profile)| {
+            let target = profile.target();
+            let description = profile.description.as_deref().unwrap_or_default();
+            Item {
+                name: name.as_ref(),
+                haystack: format!(
+                    "{} {} {} {}",
+                    name,
+                    target,
+                    profile.account_id().unwrap_or_default(),
+                    description
+                )
+                .to_lowercase(),
+                target,
+                description,
+                cached: cached.contains(&name.as_ref()),
+            }
+        })
+        .collect();
+
+    terminal::enable_raw_mode().map_err(|e| format!("cannot use the terminal: {}", e))?;
+    let mut out = stderr();
+    let result = execute!(out, terminal::EnterAlternateScreen, cursor::Hide)
+        .and_then(|_| run_picker(&mut out, &items));
+    let _ = execute!(out, cursor::Show, terminal::LeaveAlternateScreen);
+    let _ = terminal::disable_raw_mode();
+    result.map_err(|e| format!("cannot use the terminal: {}", e))
+}
+
+fn run_picker(out: &mut Stderr, items: &[Item]) -> std::io::Result<Option<String>> {
+    let mut query = String::new();
+    let mut selected = 0;
+    loop {
+        let matches = filter(items, &query);
+        selected = selected.min(matches.len().saturating_sub(1));
+        draw(out, &query, &matches, items.len(), selected)?;
+
+        let key = match event::read()? {
+            Event::Key(key) if key.kind != KeyEventKind::Release => key,
+            _ => continue,
+        };
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key {
+            KeyEvent {
+                code: KeyCode::Esc, ..
+            } => return Ok(None),
+            KeyEvent {
+                code: KeyCode::Char('c'),
+                ..
+            } if ctrl => return Ok(None),
+            KeyEvent {
+                code: KeyCode::Enter,
+                ..
+            } => return Ok(matches.get(selected).map(|i| i.name.to_owned())),
+            KeyEvent {
+                code: KeyCode::Up, ..
+            } => selected = selected.saturating_sub(1),
+            KeyEvent {
+                code: KeyCode::Char('p'),
+                ..
+            } if ctrl => selected = selected.saturating_sub(1),
+            KeyEvent {
+                code: KeyCode::Down,
+                ..
+            } => selected += 1,
+            KeyEvent {
+                code: KeyCode::Char('n'),
+                ..
+            } if ctrl => selected += 1,
+            KeyEvent {
+                code: KeyCode::Backspace,
+                ..
+            } => {
+                query.pop();
+                selected = 0;
+            }
+            KeyEvent {
+                code: KeyCode::Char(c),
+                ..
+            } if !ctrl => {
+                query.push(c);
+                selected = 0;
+            }
+            _ => {}
+        }
+    }
+}
+
+fn draw(
+    out: &mut Stderr,
+    query: &str,
+    matches: &[&Item],
+    total: usize,
+    selected: usize,
+) -> std::io::Result<()> {
+    // some terminals, e.g. a bare pty, report no size
+    let (width, height) = match terminal::size()? {
+        (0, _) | (_, 0) => (80, 24),
+        size => size,
+    };
+    let width = width as usize;
+    let rows = (height as usize).saturating_sub(2);
+    let name_width = matches.iter().map(|i| i.name.len()).max().unwrap_or(0) + 2;
+    let first = selected.saturating_sub(rows.saturating_sub(1));
+
+    queue!(
+        out,
+        terminal::Clear(terminal::ClearType::All),
+        cursor::MoveTo(0, 0),
+        SetAttribute(Attribute::Bold),
+        Print("> "),
+        SetAttribute(Attribute::Reset),
+        Print(query),
+        cursor::MoveTo(0, 1),
+        SetForegroundColor(Color::DarkGrey),
+        Print(format!(
+            "  {}/{} profiles, * has valid credentials",
+            matches.len(),
+            total
+        )),
+        SetForegroundColor(Color::Reset),
+    )?;
+    for (row, (ix, item)) in matches
+        .iter()
+        .enumerate()
+        .skip(first)
+        .take(rows)
+        .enumerate()
+    {
+        let marker = if item.cached { '*' } else { ' ' };
+        let mut line = format!(
+            "{}{} {:width$}{}",
+            if ix == selected { '>' } else { ' ' },
+            marker,
+            item.name,
+            item.target,
+            width = name_width
+        );
+        if !item.description.is_empty() {
+            line.push_str("  ");
+            line.push_str(item.description);
+        }
+        let line: String = line.chars().take(width).collect();
+        queue!(out, cursor::MoveTo(0, row as u16 + 2))?;
+        if ix == selected {
+            queue!(
+                out,
+                SetAttribute(Attribute::Reverse),
+                Print(line),
+                SetAttribute(Attribute::Reset)
+            )?;
+        } else if item.cached {
+            queue!(
+                out,
+                SetForegroundColor(Color::Green),
+                Print(line),
+                SetForegroundColor(Color::Reset)
+            )?;
+        } else {
+            queue!(out, Print(line))?;
+        }
+    }
+    out.flush()
+}
+
+/// The items matching all words of the query, best matches first.
+fn filter<'a, 'b>(items: &'a [Item<'b>], query: &str) -> Vec<&'a Item<'b>> {
+    let words: Vec<String> = query.split_whitespace().map(str::to_lowercase).collect();
+    let mut scored: Vec<(usize, &Item)> = items
+        .iter()
+        .filter_map(|item| {
+            words
+                .iter()
+                .map(|w| score(&item.haystack, w))
+                .sum::<Option<usize>>()
+                .map(|s| (s, item))
+        })
+        .collect();
+    scored.sort_by_key(|(s, _)| *s);
+    scored.into_iter().map(|(_, item)| item).collect()
+}
+
+/// Matches the characters of `word` in order within `text`.
+/// Lower scores are better: gaps between the characters and a late start cost points.
+fn score(text: &str, word: &str) -> Option<usize> {
+    let mut score = 0;
+    let mut chars = text.char_indices();
+    let mut last: Option<usize> = None;
+    for wc in word.chars() {
+        let (ix, _) = chars.find(|(_, c)| *c == wc)?;
+        score += match last {
+            Some(l) => ix - l - 1,
+            None => ix.min(10),
+        };
+        last = Some(ix);
+    }
+    Some(score)
+}
+
+#[test]
+fn fuzzy_filter() {
+    let item = |name: &'static str, target: &str| Item {
+        name,
+        target: target.to_owned(),
+        description: "",
+        haystack: format!("{} {}", name, target),
+        cached: false,
+    };
+    let items = vec![
+        item("prod-admin", "arn:aws:iam::111:role/admin"),
+        item("dev-admin", "arn:aws:iam::222:role/admin"),
+        item("dev-read", "arn:aws:iam::222:role/readonly"),
+    ];
+    let names =
+        |query: &str| -> Vec<&str> { filter(&items, query).iter().map(|i| i.name).collect() };
+
+    assert_eq!(names(""), vec!["prod-admin", "dev-admin", "dev-read"]);
+    assert_eq!(names("dev"), vec!["dev-admin", "dev-read"]);
+    assert_eq!(names("dvrd")[0], "dev-read");
+    assert_eq!(names("222 admin"), vec!["dev-admin"]);
+    assert_eq!(names("readonly"), vec!["dev-read"]);
+    assert!(names("xyz").is_empty());
+}
@@ -0,0 +1,197 @@
+use std::env;
+
+use ansi_term::Style;
+use chrono::{DateTime, Duration, Local, Utc};
+
+use crate::config::{self, Config, PromptConfig};
+use crate::credentials::{CredentialExpirations, ProfileName};
+use crate::util;
+
+const PLACEHOLDERS: [&str; 6] = [
+    "profile",
+    "account",
+    "region",
+    "remaining",
+    "expires_at",
+    "role",
+];
+
+/// Prints the prompt part for the current profile as configured in the `[prompt]` section.
+pub fn print_prompt(color: bool) {
+    let config = read_config();
+    let prompt = config
+        .as_ref()
+        .map_or_else(PromptConfig::default, |c| c.prompt.clone());
+    let paint = |style: Style, text: &str| paint(color, style, text);
+
+    let profile = match util::current_profile() {
+        Some(p) => p,
+        None => {
+            if !prompt.hide_when_unset {
+                print!("{}", prompt.unset_text);
+            }
+            return;
+        }
+    };
+    let expiration = match CredentialExpirations::get(&profile) {
+        Ok(e) => e,
+        Err(e) => {
+            eprintln!("ERROR: {}", e);
+            return;
+        }
+    };
+    let (remaining, style) = remaining(config.as_ref(), &profile, expiration, &prompt);
+    let name = ProfileName::new(&profile);
+    let profile_config = config.as_ref().and_then(|c| c.profiles.get(&name));
+
+    let out = render(&prompt.format, |placeholder| {
+        Some(match placeholder {
+            "profile" => paint(prompt.profile_style, &profile),
+            "account" => profile_config
+                .and_then(|p| p.account_id())
+                .map(|id| prompt.account_aliases.get(id).map_or(id, String::as_str))
+                .unwrap_or_default()
+                .to_owned(),
+            "region" => env::var("AWS_REGION")
+                .or_else(|_| env::var("AWS_DEFAULT_REGION"))
+                .ok()
+                .or_else(|| {
+                    config
+                        .as_ref()
+                        .map(|c| c.region_of(&name).name().to_owned())
+                })
+                .unwrap_or_default(),
+            "remaining" => paint(style, &remaining),
+            "expires_at" => match expiration {
+                Some(ex) => paint(style, &ex.with_timezone(&Local).format("%H:%M").to_string()),
+                None => paint(style, &remaining),
+            },
+            "role" => profile_config
+                .and_then(|p| p.role_name())
+                .unwrap_or_default()
+                .to_owned(),
+            _ => return None,
+        })
+    });
+    print!("{}", out);
+}
+
+/// Prints only the remaining time of the current profile.
+pub fn print_expiration(color: bool) {
+    if let Some(profile) = util::current_profile() {
+        let config = read_config();
+        let prompt = config
+            .as_ref()
+            .map_or_else(PromptConfig::default, |c| c.prompt.clone());
+        match CredentialExpirations::get(&profile) {
+            Ok(expiration) => {
+                let (remaining, style) = remaining(config.as_ref(), &profile, expiration, &prompt);
+                print!("{}", paint(color, style, &remaining))
+            }
+            Err(e) => eprintln!("ERROR: {}", e),
+        }
+    }
+}
+
+/// The prompt must never fail, so an unreadable config falls back to the defaults.
+fn read_config() -> Option<Config> {
+    Config::read().ok().flatten()
+}
+
+/// The remaining time and its style, depending on whether the credentials
+/// are fresh, about to be refreshed or expired.
+fn remaining(
+    config: Option<&Config>,
+    profile: &str,
+    expiration: Option<DateTime<Utc>>,
+    prompt: &PromptConfig,
+) -> (String, Style) {
+    let refresh = config.map_or(
+        Duration::minutes(config::DEFAULT_REFRESH_BEFORE_EXPIRY_MINUTES),
+        |c| c.refresh_before_expiry_of(&ProfileName::new(profile)),
+    );
+    match expiration.map(|ex| ex - Utc::now()) {
+        Some(d) if d > refresh => (util::format_duration(d), prompt.fresh_style),
+        Some(d) if d > Duration::zero() => (util::format_duration(d), prompt.expiring_style),
+        _ => ("expired".to_owned(), prompt.expired_style),
+    }
+}
+
+fn paint(color: bool, style: Style, text: &str) -> String {
+    // https://no-color.org
+    if color && env::var_os("NO_COLOR").unwrap_or_default().is_empty() {
+        style.paint(text).to_string()
+    } else {
+        text.to_owned()
+    }
+}
+
+/// Replaces the `{placeholder}`s of the format, unknown ones are kept as they are.
+fn render(format: &str, mut value: impl FnMut(&str) -> Option<String>) -> String {
+    let mut out = String::with_capacity(format.len());
+    let mut rest = format;
+    while let Some(start) = rest.find('{') {
+        let end = match rest[start..].find('}') {
+            Some(end) => start + end,
+            None => break,
+        };
+        out.push_str(&rest[..start]);
+        match value(&rest[start + 1..end]) {
+            Some(v) => out.push_str(&v),
+            None => out.push_str(&rest[start..=end]),
+        }
+        rest = &rest[end + 1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+pub fn check_format(format: &str) -> Result<(), String> {
+    let mut unknown = Vec::new();
+    render(format, |placeholder| {
+        if !PLACEHOLDERS.contains(&placeholder) {
+            unknown.push(format!("{{{}}}", placeholder));
+        }
+        None
+    });
+    if unknown.is_empty() {
+        Ok(())
+    } else {
+        Err(format!(
+            "unknown prompt placeholders {}, expected {}",
+            unknown.join(", "),
+            PLACEHOLDERS
+                .iter()
+                .map(|p| format!("{{{}}}", p))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ))
+    }
+}
+
+#[test]
+fn render_format() {
+    use crate::styles;
+    use ansi_term::Color;
+
+    let value = |placeholder: &str| match placeholder {
+        "profile" => Some("dev".to_owned()),
+        "remaining" => Some("0:42".to_owned()),
+        _ => None,
+    };
+    assert_eq!(render("[{profile} {remaining}]", value), "[dev 0:42]");
+    assert_eq!(render("{profile}{x} {", value), "dev{x} {");
+    assert!(check_format("{profile}@{account} {region} {role} {expires_at}").is_ok());
+    assert!(check_format("{profile} {accont}").is_err());
+
+    assert_eq!(
+        styles::parse("bold yellow"),
+        Ok(Style::new().bold().fg(Color::Yellow))
+    );
+    assert_eq!(
+        styles::parse("#ff8800"),
+        Ok(Style::new().fg(Color::RGB(255, 136, 0)))
+    );
+    assert_eq!(styles::parse("208"), Ok(Style::new().fg(Color::Fixed(208))));
+    assert!(styles::parse("bold yelow").is_err());
+}
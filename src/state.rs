@@ -14,8 +14,8 @@ pub struct State {
 
 impl State {
     pub fn read() -> Self {
-        match fs::read_to_string(state_file_path()) {
-            Ok(c) => toml::from_str(&c).expect("valid state"),
+        match state_file_path().map(fs::read_to_string) {
+            Ok(Ok(c)) => toml::from_str(&c).expect("valid state"),
             _ => Self {
                 last_version_check_time: Utc.timestamp(0, 0),
                 last_credentials_rotation_time: None,
@@ -25,16 +25,11 @@ impl State {
 
     pub fn save(&self) -> Result<(), String> {
         let content = toml::to_string(&self).expect("encoded TOML string");
-        util::create_storage_dir();
-        let sf = state_file_path();
-        fs::write(&sf, content).map_err(|e| format!("Cannot write file: {}", e))?;
-        util::set_permissions(&sf, 0o600);
-        Ok(())
+        util::create_storage_dir()?;
+        util::write_atomically(&state_file_path()?, &content, 0o600)
     }
 }
 
-const STATE_FILE_PATH: &str = "~/.local/share/awscredx/state.toml";
-
-fn state_file_path() -> PathBuf {
-    util::path_to_absolute(STATE_FILE_PATH)
+fn state_file_path() -> Result<PathBuf, String> {
+    Ok(util::storage_dir()?.join("state.toml"))
 }
@@ -12,3 +12,38 @@ pub fn number() -> Style {
 pub fn error() -> Style {
     Style::new().fg(Color::Red).bold()
 }
+
+/// Parses a style like "bold yellow", "green", "italic 208" or "#ff8800".
+/// Numbers are colors of the 256 color palette.
+pub fn parse(spec: &str) -> Result<Style, String> {
+    let mut style = Style::new();
+    for word in spec.split_whitespace() {
+        style = match word.to_lowercase().as_str() {
+            "bold" => style.bold(),
+            "dimmed" => style.dimmed(),
+            "italic" => style.italic(),
+            "underline" => style.underline(),
+            "black" => style.fg(Color::Black),
+            "red" => style.fg(Color::Red),
+            "green" => style.fg(Color::Green),
+            "yellow" => style.fg(Color::Yellow),
+            "blue" => style.fg(Color::Blue),
+            "purple" | "magenta" => style.fg(Color::Purple),
+            "cyan" => style.fg(Color::Cyan),
+            "white" => style.fg(Color::White),
+            w => style.fg(parse_color(w).ok_or(format!("unknown style '{}' in '{}'", w, spec))?),
+        };
+    }
+    Ok(style)
+}
+
+fn parse_color(word: &str) -> Option<Color> {
+    if let Some(hex) = word.strip_prefix('#') {
+        if hex.len() != 6 {
+            return None;
+        }
+        let channel = |ix: usize| u8::from_str_radix(hex.get(ix..ix + 2)?, 16).ok();
+        return Some(Color::RGB(channel(0)?, channel(2)?, channel(4)?));
+    }
+    word.parse().ok().map(Color::Fixed)
+}
@@ -1,4 +1,7 @@
+use std::fs::{File, OpenOptions};
+use std::io::Write;
 use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
 use std::{env, fs};
 
 use ansi_term::{Color, Style};
@@ -6,10 +9,101 @@ use reqwest::Proxy;
 
 use crate::version::VERSION;
 
-pub fn path_to_absolute(path: &str) -> PathBuf {
-    let home = env::var("HOME").expect("HOME is not set");
-    let abs = path.replace("~", &home).replace("$HOME", &home);
-    PathBuf::from(abs)
+/// Expands a leading `~` or `$HOME`, other paths are returned unchanged.
+pub fn path_to_absolute(path: &str) -> Result<PathBuf, String> {
+    expand_home(path, &|name| env::var(name).ok())
+}
+
+fn expand_home(path: &str, var: &dyn Fn(&str) -> Option<String>) -> Result<PathBuf, String> {
+    let rest = match path
+        .strip_prefix('~')
+        .or_else(|| path.strip_prefix("$HOME"))
+    {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => rest,
+        _ => return Ok(PathBuf::from(path)),
+    };
+    let home = var("HOME")
+        .filter(|x| !x.is_empty())
+        .ok_or(format!("HOME is not set, cannot resolve {}", path))?;
+    Ok(PathBuf::from(format!("{}{}", home, rest)))
+}
+
+static CONFIG_FILE: OnceLock<PathBuf> = OnceLock::new();
+
+/// Overrides the configuration file location, e.g. from the `--config` flag.
+pub fn set_config_file(path: &str) -> Result<(), String> {
+    CONFIG_FILE
+        .set(path_to_absolute(path)?)
+        .expect("config file is set only once");
+    Ok(())
+}
+
+/// The configuration file given by `--config`, if any.
+pub fn config_file_override() -> Option<&'static Path> {
+    CONFIG_FILE.get().map(PathBuf::as_path)
+}
+
+/// The path in the environment variable, an empty variable counts as unset.
+pub fn env_path(name: &str) -> Result<Option<PathBuf>, String> {
+    env_path_with(name, &|name| env::var(name).ok())
+}
+
+fn env_path_with(
+    name: &str,
+    var: &dyn Fn(&str) -> Option<String>,
+) -> Result<Option<PathBuf>, String> {
+    match var(name).filter(|x| !x.is_empty()) {
+        Some(path) => expand_home(&path, var).map(Some),
+        None => Ok(None),
+    }
+}
+
+/// The path in the environment variable or the default path.
+fn env_path_or(name: &str, default: &str) -> Result<PathBuf, String> {
+    match env_path(name)? {
+        Some(path) => Ok(path),
+        None => path_to_absolute(default),
+    }
+}
+
+/// `$XDG_CONFIG_HOME` or `~/.config`.
+pub fn xdg_config_dir() -> Result<PathBuf, String> {
+    env_path_or("XDG_CONFIG_HOME", "~/.config")
+}
+
+/// `--config` flag, `$XDG_CONFIG_HOME/awscredx/config.toml` or `~/.config/awscredx/config.toml`.
+pub fn config_file() -> Result<PathBuf, String> {
+    match CONFIG_FILE.get() {
+        Some(path) => Ok(path.clone()),
+        None => Ok(xdg_config_dir()?.join("awscredx").join("config.toml")),
+    }
+}
+
+/// `$XDG_DATA_HOME/awscredx` or `~/.local/share/awscredx`.
+pub fn storage_dir() -> Result<PathBuf, String> {
+    Ok(env_path_or("XDG_DATA_HOME", "~/.local/share")?.join("awscredx"))
+}
+
+/// `$AWS_SHARED_CREDENTIALS_FILE` or `~/.aws/credentials`.
+pub fn credentials_file() -> Result<PathBuf, String> {
+    env_path_or("AWS_SHARED_CREDENTIALS_FILE", "~/.aws/credentials")
+}
+
+/// `$AWS_CONFIG_FILE` or `~/.aws/config`.
+pub fn aws_config_file() -> Result<PathBuf, String> {
+    env_path_or("AWS_CONFIG_FILE", "~/.aws/config")
+}
+
+/// Formats the duration as "h:mm".
+pub fn format_duration(d: chrono::Duration) -> String {
+    format!("{}:{:02}", d.num_hours(), d.num_minutes() % 60)
+}
+
+/// The currently assumed profile, `AWS_PROFILE` takes precedence over `AWSCREDX_PROFILE`.
+pub fn current_profile() -> Option<String> {
+    env::var("AWS_PROFILE")
+        .or_else(|_| env::var(crate::init::PROFILE_VAR))
+        .ok()
 }
 
 #[cfg(target_family = "unix")]
@@ -20,7 +114,66 @@ pub fn set_permissions(path: &Path, mode: u32) {
 }
 
 #[cfg(target_family = "windows")]
-pub fn set_permissions(_path: &PathBuf, _mode: u32) {}
+pub fn set_permissions(_path: &Path, _mode: u32) {}
+
+/// Writes the file by renaming a fully written temporary file over it,
+/// so that readers never observe a partially written file.
+/// An existing file keeps its permissions, a new one gets `mode`.
+/// Symlinks are resolved, so that the file they point to is replaced and not the link.
+pub fn write_atomically(path: &Path, content: &str, mode: u32) -> Result<(), String> {
+    let path = &fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let file_name = path
+        .file_name()
+        .ok_or(format!("{} is not a file path", path.display()))?;
+    let tmp = path.with_file_name(format!(".{}.tmp", file_name.to_string_lossy()));
+    let permissions = fs::metadata(path).map(|m| m.permissions()).ok();
+
+    let write = || -> std::io::Result<()> {
+        let mut file = File::create(&tmp)?;
+        match &permissions {
+            Some(p) => fs::set_permissions(&tmp, p.clone())?,
+            None => set_permissions(&tmp, mode),
+        }
+        file.write_all(content.as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&tmp, path)
+    };
+    write().map_err(|e| {
+        let _ = fs::remove_file(&tmp);
+        format!("Cannot write file {}: {}", path.display(), e)
+    })
+}
+
+/// An exclusive advisory lock that is held until the value is dropped.
+pub struct FileLock(File);
+
+impl FileLock {
+    pub fn acquire(path: &Path) -> Result<Self, String> {
+        use fs2::FileExt;
+        if let Some(dir) = path.parent() {
+            if !dir.as_os_str().is_empty() && !dir.exists() {
+                fs::create_dir_all(dir)
+                    .map_err(|e| format!("Cannot create directory {}: {}", dir.display(), e))?;
+                set_permissions(dir, 0o700);
+            }
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(path)
+            .map_err(|e| format!("Cannot open lock file {}: {}", path.display(), e))?;
+        file.lock_exclusive()
+            .map_err(|e| format!("Cannot lock file {}: {}", path.display(), e))?;
+        Ok(FileLock(file))
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        let _ = fs2::FileExt::unlock(&self.0);
+    }
+}
 
 pub fn get_https_proxy() -> Option<String> {
     std::env::var_os("https_proxy")
@@ -47,12 +200,55 @@ pub fn styled_error_word() -> String {
     err_style.paint("ERROR").to_string()
 }
 
-pub const STORAGE_DIR: &str = "~/.local/share/awscredx";
-
-pub fn create_storage_dir() {
-    let dir = path_to_absolute(STORAGE_DIR);
+pub fn create_storage_dir() -> Result<PathBuf, String> {
+    let dir = storage_dir()?;
     if !dir.exists() {
-        fs::create_dir_all(&dir).expect("dir created");
+        fs::create_dir_all(&dir)
+            .map_err(|e| format!("Cannot create directory {}: {}", dir.display(), e))?;
         set_permissions(&dir, 0o700);
     }
+    Ok(dir)
+}
+
+#[test]
+fn resolve_env_paths() {
+    let var = |name: &str| match name {
+        "HOME" => Some("/home/me".to_owned()),
+        "ABSOLUTE" => Some("/data/a~b/credentials".to_owned()),
+        "TILDE" => Some("~/creds".to_owned()),
+        "HOME_VAR" => Some("$HOME/creds".to_owned()),
+        "EMPTY" => Some(String::new()),
+        _ => None,
+    };
+    let path = |name: &str| env_path_with(name, &var).map(|p| p.map(PathBuf::into_os_string));
+    assert_eq!(path("ABSOLUTE"), Ok(Some("/data/a~b/credentials".into())));
+    assert_eq!(path("TILDE"), Ok(Some("/home/me/creds".into())));
+    assert_eq!(path("HOME_VAR"), Ok(Some("/home/me/creds".into())));
+    assert_eq!(path("EMPTY"), Ok(None));
+    assert_eq!(path("UNSET"), Ok(None));
+
+    let no_home = |name: &str| var(name).filter(|_| name != "HOME");
+    assert_eq!(
+        env_path_with("ABSOLUTE", &no_home),
+        Ok(Some(PathBuf::from("/data/a~b/credentials")))
+    );
+    assert!(env_path_with("TILDE", &no_home).is_err());
+}
+
+#[cfg(target_family = "unix")]
+#[test]
+fn write_through_symlink() {
+    const TARGET: &str = "./test.symlink.target";
+    const LINK: &str = "./test.symlink";
+    fs::write(TARGET, "old").unwrap();
+    std::os::unix::fs::symlink("test.symlink.target", LINK).unwrap();
+
+    let result = write_atomically(Path::new(LINK), "new", 0o600);
+    let is_link = fs::symlink_metadata(LINK).map(|m| m.file_type().is_symlink());
+    let content = fs::read_to_string(TARGET);
+    fs::remove_file(LINK).unwrap();
+    fs::remove_file(TARGET).unwrap();
+    assert_eq!(result, Ok(()));
+    assert!(is_link.unwrap());
+    assert_eq!(content.unwrap(), "new");
 }
@@ -1,5 +1,5 @@
 use std::collections::HashMap;
-use std::{env, process};
+use std::process;
 
 use rusoto_credential::AwsCredentials;
 use serde::Serialize;
@@ -9,6 +9,45 @@ use crate::credentials::{CredentialsFile, ProfileName};
 use crate::util;
 
 const SIGN_IN_URL: &str = "https://signin.aws.amazon.com/federation";
+const DEFAULT_SESSION_DURATION_SECONDS: i64 = 3600;
+
+/// Service names offered by the shell completion of `--service`.
+pub const SERVICES: &[&str] = &[
+    "acm",
+    "apigateway",
+    "athena",
+    "cloudformation",
+    "cloudfront",
+    "cloudtrail",
+    "cloudwatch",
+    "codebuild",
+    "codepipeline",
+    "cognito",
+    "console",
+    "dynamodb",
+    "ec2",
+    "ecr",
+    "ecs",
+    "eks",
+    "elasticache",
+    "es",
+    "events",
+    "glue",
+    "iam",
+    "kinesis",
+    "kms",
+    "lambda",
+    "rds",
+    "route53",
+    "s3",
+    "secretsmanager",
+    "ses",
+    "sns",
+    "sqs",
+    "ssm",
+    "states",
+    "vpc",
+];
 
 #[derive(Serialize)]
 struct SessionData<'a> {
@@ -22,7 +61,7 @@ struct SessionData<'a> {
     token: Option<&'a String>,
 }
 
-fn build_sign_in_token_url(cred: &AwsCredentials) -> String {
+fn build_sign_in_token_url(cred: &AwsCredentials, duration_seconds: i64) -> String {
     let session_json = serde_json::to_string(&SessionData {
         id: cred.aws_access_key_id(),
         key: cred.aws_secret_access_key(),
@@ -33,7 +72,7 @@ fn build_sign_in_token_url(cred: &AwsCredentials) -> String {
     format!(
         "{}?Action=getSigninToken&DurationSeconds={}&SessionType=json&{}",
         SIGN_IN_URL,
-        3600,
+        duration_seconds,
         serde_urlencoded::to_string(&[("Session", session_json)]).unwrap()
     )
 }
@@ -46,12 +85,19 @@ pub fn create_signin_url(aws_service_name: &str, open_in_browser: bool) {
 }
 
 fn create(aws_service_name: &str, open_in_browser: bool) -> Result<(), String> {
+    let config = Config::read()?.ok_or("Config file does not exist")?;
     let cred_file = CredentialsFile::read_default()?;
-    let profile = env::var("AWS_PROFILE").map_err(|_e| "AWS_PROFILE is not set")?;
+    let profile = util::current_profile().ok_or("AWS_PROFILE is not set")?;
+    let profile = ProfileName::new(&profile);
     let cred = cred_file
-        .get_credentials(&ProfileName::new(&profile))
+        .get_credentials(&profile)
         .ok_or(format!("Profile {} is expired", profile))?;
-    let sign_in_url = build_sign_in_token_url(cred);
+    let duration = config
+        .profiles
+        .get(&profile)
+        .and_then(|p| p.session_duration)
+        .map_or(DEFAULT_SESSION_DURATION_SECONDS, |d| d.num_seconds());
+    let sign_in_url = build_sign_in_token_url(cred, duration);
 
     let client = util::get_https_client()?;
     let response: HashMap<String, String> = client
@@ -65,16 +111,15 @@ fn create(aws_service_name: &str, open_in_browser: bool) -> Result<(), String> {
         .get("SigninToken")
         .ok_or("AWS sign in response does not contain sign in token")?;
 
-    let config = Config::read()?.ok_or("Config file does not exist")?;
     let destination_url = format!(
         "https://{region}.console.aws.amazon.com/{}/home?region={region}",
         aws_service_name,
-        region = config.region.name()
+        region = config.region_of(&profile).name()
     );
     let sign_in_url = format!(
         "{}?{}",
         SIGN_IN_URL,
-        serde_urlencoded::to_string(&[
+        serde_urlencoded::to_string([
             ("Action", "login"),
             ("Issuer", ""),
             ("Destination", &destination_url),